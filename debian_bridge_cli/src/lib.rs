@@ -6,6 +6,7 @@ extern crate pretty_env_logger;
 extern crate xdg;
 
 mod matcher;
+mod progress;
 mod starter;
 
 pub use matcher::*;
@@ -37,4 +37,20 @@ impl<'a> CommandMatcher<'a> {
             .value_of(&arg)
             .map(|s| s.to_string())
     }
+
+    pub fn get_arguments<T, S>(&self, command: T, arg: S) -> Vec<String>
+    where
+        T: Into<String>,
+        S: Into<String>,
+    {
+        let command = command.into();
+        let arg = arg.into();
+
+        self.matches
+            .subcommand_matches(&command)
+            .expect(format!("No command '{}' presented", command).as_str())
+            .values_of(&arg)
+            .map(|values| values.map(|s| s.to_string()).collect())
+            .unwrap_or(vec![])
+    }
 }
@@ -0,0 +1,85 @@
+use debian_bridge_core::BuildProgress;
+use std::{
+    io::{stderr, Write},
+    sync::Mutex,
+};
+
+const BAR_WIDTH: usize = 30;
+const INSTRUCTION_WIDTH: usize = 50;
+
+/// Renders image build progress as a single terminal line
+pub struct ProgressBar {
+    line: Mutex<String>,
+}
+
+impl ProgressBar {
+    pub fn new() -> Self {
+        ProgressBar {
+            line: Mutex::new(String::new()),
+        }
+    }
+
+    pub fn update(&self, progress: &BuildProgress) {
+        let mut line = self.line.lock().unwrap();
+
+        match progress {
+            BuildProgress::Step {
+                current,
+                total,
+                instruction,
+            } => {
+                let filled = match total {
+                    0 => 0,
+                    total => BAR_WIDTH * current.min(total) / total,
+                };
+
+                *line = format!(
+                    "[{}{}] {}/{} {}",
+                    "=".repeat(filled),
+                    " ".repeat(BAR_WIDTH - filled),
+                    current,
+                    total,
+                    instruction
+                        .chars()
+                        .take(INSTRUCTION_WIDTH)
+                        .collect::<String>()
+                );
+                Self::draw(&line, None);
+            }
+            BuildProgress::Layer {
+                id,
+                status,
+                current: Some(current),
+                total: Some(total),
+            } => Self::draw(
+                &line,
+                Some(format!(
+                    "{}: {} {:.1}/{:.1} MB",
+                    id,
+                    status,
+                    *current as f64 / 1_000_000.0,
+                    *total as f64 / 1_000_000.0
+                )),
+            ),
+            BuildProgress::Layer { id, status, .. } => {
+                Self::draw(&line, Some(format!("{}: {}", id, status)))
+            }
+            BuildProgress::Output(output) => debug!("{}", output),
+        }
+    }
+
+    pub fn finish(&self) {
+        if !self.line.lock().unwrap().is_empty() {
+            eprintln!();
+        }
+    }
+
+    fn draw(line: &String, detail: Option<String>) {
+        match detail {
+            Some(detail) => eprint!("\r\x1b[2K{} | {}", line, detail),
+            None => eprint!("\r\x1b[2K{}", line),
+        }
+
+        stderr().flush().unwrap_or_default();
+    }
+}
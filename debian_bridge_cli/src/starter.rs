@@ -1,11 +1,15 @@
-use crate::CommandMatcher;
+use crate::{progress::ProgressBar, CommandMatcher};
 use clap::{App, AppSettings, ArgMatches};
-use debian_bridge_core::{App as Wrapper, Config, Docker, Feature, Icon, Program, System};
+use debian_bridge_core::{
+    engine, App as Wrapper, Config, Endpoint, EngineKind, Executor, Feature, Icon, Mount, Program,
+    RunOptions, System,
+};
 use std::{
     error::Error,
     net::IpAddr,
     path::{Path, PathBuf},
     str::FromStr,
+    sync::Arc,
 };
 
 pub fn start<T, S, U>(package_name: T, authors: S, version: U)
@@ -63,16 +67,32 @@ where
     debug!("Cache path: {}", cache_path.to_str().unwrap());
 
     let matcher = CommandMatcher::new(&matches);
-    let docker = Docker::new();
     let config = Config::deserialize(config_path.as_path())?;
-    let system = System::try_new(&docker)?;
+    let endpoint = match matches.value_of("docker-host") {
+        Some(host) => Endpoint {
+            host: Some(host.to_string()),
+            ..config.docker.to_owned()
+        },
+        None => config.docker.to_owned(),
+    };
+
+    // the docker API client reads TLS files from the environment, set before runtime threads
+    if config.engine == EngineKind::Docker {
+        for (name, value) in endpoint.resolve()?.tls_env() {
+            std::env::set_var(name, value);
+        }
+    }
+
+    let executor = Executor::new()?;
+    let engine = engine::connect(&config.engine, &endpoint, &executor)?;
+    let system = System::try_new(engine.as_ref())?;
     let mut app = Wrapper::new(
         &package_name,
         &package_name,
         &cache_path,
         &config,
         &system,
-        &docker,
+        engine.as_ref(),
     );
 
     debug!("Subcommand processing...");
@@ -83,13 +103,22 @@ where
             println!("Available features: {}", app.features);
         }
         Some("create") => {
-            app.create(
+            let progress = Arc::new(ProgressBar::new());
+            let handler = progress.clone();
+
+            app.set_progress_handler(Arc::new(move |event| handler.update(event)));
+
+            let result = app.create(
                 get_create_package(&matcher)?.as_path(),
                 &get_create_features(&matcher),
                 &get_create_icon(&matcher),
                 &get_create_command(&matcher),
                 &get_create_deps(&matcher),
-            )?;
+                &get_create_options(&matcher)?,
+            );
+
+            progress.finish();
+            result?;
             info!("Program successfuly created");
         }
         Some("run") => {
@@ -99,8 +128,82 @@ where
                     .unwrap()
                     .value_of(&"name")
                     .unwrap(),
+                &matcher.get_arguments("run", "arguments"),
+            )?;
+        }
+        Some("shell") => {
+            app.shell(matcher.get_argument("shell", "name").unwrap())?;
+        }
+        Some("exec") => {
+            app.exec(
+                matcher.get_argument("exec", "name").unwrap(),
+                &matcher.get_arguments("exec", "command"),
             )?;
         }
+        Some("reset") => {
+            app.reset(matcher.get_argument("reset", "name").unwrap())?;
+            info!("Program successfuly reset");
+        }
+        Some("rollback") => {
+            app.rollback(
+                matcher.get_argument("rollback", "name").unwrap(),
+                &matcher.get_argument("rollback", "package-version"),
+            )?;
+        }
+        Some("update") => {
+            let progress = Arc::new(ProgressBar::new());
+            let handler = progress.clone();
+
+            app.set_progress_handler(Arc::new(move |event| handler.update(event)));
+
+            let package = std::fs::canonicalize(Path::new(
+                matcher.get_argument("update", "package").unwrap().as_str(),
+            ))?;
+            let result = app.update(
+                matcher.get_argument("update", "name").unwrap(),
+                package.as_path(),
+            );
+
+            progress.finish();
+            result?;
+            info!("Program successfuly updated");
+        }
+        Some("rebuild") => {
+            let names = match matcher.is_option_present("rebuild", "all") {
+                true => app.list(),
+                false => vec![matcher.get_argument("rebuild", "name").unwrap()],
+            };
+            let mut failed = vec![];
+
+            for name in names {
+                info!("Rebuilding '{}'", name);
+
+                let progress = Arc::new(ProgressBar::new());
+                let handler = progress.clone();
+
+                app.set_progress_handler(Arc::new(move |event| handler.update(event)));
+
+                let result = app.rebuild(&name);
+
+                progress.finish();
+
+                match result {
+                    Ok(changes) if changes.is_empty() => println!("{}: no package changes", name),
+                    Ok(changes) => {
+                        println!("{}: {} package(s) changed", name, changes.len());
+                        changes.iter().for_each(|change| println!("\t{}", change));
+                    }
+                    Err(err) => {
+                        error!("{}: {}", name, err.to_string());
+                        failed.push(name);
+                    }
+                }
+            }
+
+            if !failed.is_empty() {
+                return Err(format!("Failed to rebuild: {}", failed.join(", ")).into());
+            }
+        }
         Some("remove") => {
             app.remove(
                 matches
@@ -111,6 +214,32 @@ where
             )?;
             info!("Program successfuly removed");
         }
+        Some("gc") => {
+            let dry_run = matcher.is_option_present("gc", "dry-run");
+            let garbage = app.gc(dry_run)?;
+
+            match (garbage.is_empty(), dry_run) {
+                (true, _) => println!("Nothing to remove"),
+                (false, true) => println!("Would remove:"),
+                (false, false) => println!("Removed:"),
+            }
+
+            garbage.iter().for_each(|garbage| println!("\t{}", garbage));
+        }
+        Some("export-image") => {
+            app.export_image(
+                matcher.get_argument("export-image", "name").unwrap(),
+                Path::new(&matcher.get_argument("export-image", "file").unwrap()),
+            )?;
+            info!("Program successfuly exported");
+        }
+        Some("import-image") => {
+            let name = app.import_image(Path::new(
+                &matcher.get_argument("import-image", "file").unwrap(),
+            ))?;
+
+            info!("Program '{}' successfuly imported", name);
+        }
         Some("list") => {
             let list = app.list().join(", ");
 
@@ -148,6 +277,10 @@ fn get_create_features(matcher: &CommandMatcher) -> Vec<Feature> {
         features.push(Feature::HomePersistent);
     }
 
+    if matcher.is_option_present("create", "home-sandbox") {
+        features.push(Feature::HomeSandbox);
+    }
+
     if matcher.is_option_present("create", "notifications") {
         features.push(Feature::Notification);
     }
@@ -160,6 +293,50 @@ fn get_create_features(matcher: &CommandMatcher) -> Vec<Feature> {
         features.push(Feature::Devices);
     }
 
+    if matcher.is_option_present("create", "persistent") {
+        features.push(Feature::Persistent);
+    }
+
+    if matcher.is_option_present("create", "printing") {
+        features.push(Feature::Printing);
+    }
+
+    if matcher.is_option_present("create", "portal") {
+        features.push(Feature::Portal);
+    }
+
+    if matcher.is_option_present("create", "clipboard") {
+        features.push(Feature::Clipboard);
+    }
+
+    if matcher.is_option_present("create", "camera") {
+        features.push(Feature::Camera);
+    }
+
+    if matcher.is_option_present("create", "audio-devices") {
+        features.push(Feature::AudioDevices);
+    }
+
+    if matcher.is_option_present("create", "usb") {
+        features.push(Feature::Usb);
+    }
+
+    if matcher.is_option_present("create", "input") {
+        features.push(Feature::Input);
+    }
+
+    if matcher.is_option_present("create", "gpu") {
+        features.push(Feature::Gpu);
+    }
+
+    if matcher.is_option_present("create", "ssh-agent") {
+        features.push(Feature::SshAgent);
+    }
+
+    if matcher.is_option_present("create", "gpg-agent") {
+        features.push(Feature::GpgAgent);
+    }
+
     features
 }
 
@@ -177,6 +354,52 @@ fn get_create_deps(matcher: &CommandMatcher) -> Option<String> {
     matcher.get_argument("create", "dependencies")
 }
 
+fn get_create_options(matcher: &CommandMatcher) -> Result<RunOptions, Box<dyn Error>> {
+    let mut options = RunOptions::default();
+
+    for share in matcher.get_arguments("create", "share") {
+        let (folder, read_only) = match share.rsplitn(2, ':').collect::<Vec<&str>>().as_slice() {
+            ["ro", folder] => (folder.to_string(), true),
+            ["rw", folder] => (folder.to_string(), false),
+            _ => (share.to_owned(), false),
+        };
+
+        options
+            .mounts
+            .push(Mount::share(Path::new(&folder), read_only)?);
+    }
+
+    for mount in matcher.get_arguments("create", "mount") {
+        let mut mount = Mount::from_str(&mount)?;
+
+        mount.source = std::fs::canonicalize(&mount.source)?;
+        options.mounts.push(mount);
+    }
+
+    for env in matcher.get_arguments("create", "env") {
+        let mut parts = env.splitn(2, '=');
+        let name = parts.next().unwrap_or_default().to_string();
+        let value = parts
+            .next()
+            .ok_or(format!(
+                "Invalid environment variable '{}', expected KEY=VAL",
+                env
+            ))?
+            .to_string();
+
+        options.env.push((name, value));
+    }
+
+    for device in matcher.get_arguments("create", "device") {
+        options.devices.push(PathBuf::from(device));
+    }
+
+    options.dbus_proxy = matcher.is_option_present("create", "dbus-proxy");
+    options.electron = matcher.is_option_present("create", "electron");
+
+    Ok(options)
+}
+
 fn get_create_icon(matcher: &CommandMatcher) -> Option<Icon> {
     let icon_owned = matcher.get_argument("create", "desktop-icon");
     let icon = icon_owned.as_ref().map(String::as_str);
@@ -0,0 +1,198 @@
+use super::{error::AppError, util, Mount, Program};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+type AppResult<T> = Result<T, AppError>;
+
+pub const MANIFEST_FILE: &str = "program.json";
+pub const IMAGE_FILE: &str = "image.tar";
+
+/// Portable tarball with a program image and its definition
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Bundle {
+    pub prefix: String,
+    pub program: Program,
+    /// Host home directory of the exporting user
+    #[serde(default)]
+    pub home: Option<PathBuf>,
+    /// Container home directory of the exporting user
+    #[serde(default)]
+    pub container_home: Option<PathBuf>,
+}
+
+impl Bundle {
+    pub fn new<T: Into<String>>(prefix: T, program: &Program) -> Self {
+        Bundle {
+            prefix: prefix.into(),
+            program: program.to_owned(),
+            home: dirs::home_dir(),
+            container_home: util::get_container_home().ok(),
+        }
+    }
+
+    /// Program with mounts under the homes of the exporting user moved to the same paths
+    /// under the given homes
+    pub fn rebase(&self, home: &Path, container_home: &Path) -> Program {
+        let mut program = self.program.to_owned();
+
+        program.options.mounts = program
+            .options
+            .mounts
+            .iter()
+            .map(|mount| {
+                Mount::new(
+                    &rebase_path(&mount.source, &self.home, home),
+                    &rebase_path(&mount.target, &self.container_home, container_home),
+                    mount.read_only,
+                )
+            })
+            .collect();
+
+        program
+    }
+
+    /// Writes a manifest to `dir` and packs it with the exported image into `file`
+    pub fn pack(&self, dir: &Path, file: &Path) -> AppResult<&Self> {
+        let manifest =
+            serde_json::to_string(&self).map_err(|err| AppError::File(err.to_string()))?;
+
+        std::fs::write(dir.join(MANIFEST_FILE), manifest)
+            .map_err(|err| AppError::File(err.to_string()))?;
+
+        tar(&[
+            "cf",
+            &file.to_string_lossy(),
+            "-C",
+            &dir.to_string_lossy(),
+            MANIFEST_FILE,
+            IMAGE_FILE,
+        ])?;
+
+        Ok(self)
+    }
+
+    /// Extracts a manifest and an image from `file` into `dir`
+    pub fn unpack(file: &Path, dir: &Path) -> AppResult<Self> {
+        tar(&[
+            "xf",
+            &file.to_string_lossy(),
+            "-C",
+            &dir.to_string_lossy(),
+            MANIFEST_FILE,
+            IMAGE_FILE,
+        ])?;
+
+        let manifest = std::fs::read_to_string(dir.join(MANIFEST_FILE))
+            .map_err(|err| AppError::File(err.to_string()))?;
+
+        serde_json::from_str(&manifest).map_err(|err| AppError::File(err.to_string()))
+    }
+}
+
+fn rebase_path(path: &Path, from: &Option<PathBuf>, to: &Path) -> PathBuf {
+    from.as_ref()
+        .and_then(|from| path.strip_prefix(from).ok())
+        .map_or(path.to_owned(), |relative| to.join(relative))
+}
+
+fn tar(args: &[&str]) -> AppResult<()> {
+    let output = Command::new("tar")
+        .args(args)
+        .output()
+        .map_err(|err| AppError::File(err.to_string()))?;
+
+    if !output.status.success() {
+        return Err(AppError::File(format!(
+            "Invalid program bundle: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Feature;
+
+    #[test]
+    fn bundle_unpacks_success() {
+        let mut path = std::env::temp_dir();
+        path.push("debian_bridge_bundle_test");
+
+        let (source, target) = (path.join("source"), path.join("target"));
+        let file = path.join("bundle.tar");
+        let program = Program::new(
+            "foo",
+            Path::new("/tmp/foo.deb"),
+            &vec![Feature::Display],
+            &None,
+            &Some("foo --no-sandbox".to_string()),
+            &None,
+            &Default::default(),
+        );
+
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::write(source.join(IMAGE_FILE), "image").unwrap();
+
+        Bundle::new("debian_bridge", &program)
+            .pack(&source, &file)
+            .unwrap();
+
+        let bundle = Bundle::unpack(&file, &target).unwrap();
+
+        assert_eq!("debian_bridge", bundle.prefix);
+        assert_eq!("foo", bundle.program.get_name_short());
+        assert_eq!("foo --no-sandbox", bundle.program.command);
+        assert_eq!(
+            "image",
+            std::fs::read_to_string(target.join(IMAGE_FILE)).unwrap()
+        );
+        assert!(Bundle::unpack(&source.join(IMAGE_FILE), &target).is_err());
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn bundle_rebases_mounts_success() {
+        let mut program = Program::new(
+            "foo",
+            Path::new("/tmp/foo.deb"),
+            &vec![],
+            &None,
+            &None,
+            &None,
+            &Default::default(),
+        );
+
+        program.options.mounts = vec![
+            Mount::new(
+                Path::new("/home/alice/Downloads"),
+                Path::new("/home/alice/Downloads"),
+                false,
+            ),
+            Mount::new(Path::new("/opt/data"), Path::new("/data"), true),
+        ];
+
+        let bundle = Bundle {
+            prefix: "debian_bridge".to_string(),
+            program,
+            home: Some(PathBuf::from("/home/alice")),
+            container_home: Some(PathBuf::from("/home/alice")),
+        };
+        let mounts = bundle
+            .rebase(Path::new("/var/home/bob"), Path::new("/home/bob"))
+            .options
+            .mounts;
+
+        assert_eq!(Path::new("/var/home/bob/Downloads"), mounts[0].source);
+        assert_eq!(Path::new("/home/bob/Downloads"), mounts[0].target);
+        assert_eq!(Path::new("/opt/data"), mounts[1].source);
+        assert_eq!(Path::new("/data"), mounts[1].target);
+    }
+}
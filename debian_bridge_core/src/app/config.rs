@@ -1,16 +1,19 @@
-use super::error::AppError;
+use super::{error::AppError, util};
+use crate::{sys::driver::DeviceClass, Endpoint, EngineKind};
 use serde::{Deserialize, Serialize};
 use std::{
     error::Error,
     fmt::Display,
     fs::File,
     io::{BufReader, Read},
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
+    str::FromStr,
 };
 
 pub type AppResult<T> = Result<T, AppError>;
 
 const ICON_NAME_DEFAULT: &str = "debian_bridge_default.ico";
+const KEEP_BUILDS_DEFAULT: usize = 3;
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Icon {
@@ -36,14 +39,38 @@ impl Default for Icon {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, Eq, PartialEq)]
 pub enum Feature {
     Display,
     Sound,
     Notification,
     Devices,
     HomePersistent,
+    HomeSandbox,
     Time,
+    Persistent,
+    /// CUPS printing through the host server
+    Printing,
+    /// File chooser and opening links on the host through XDG desktop portal
+    Portal,
+    /// Clipboard of the host display. X11 clients share it through the X server, wayland
+    /// clients through the compositor and XWayland bridges the two, so it can't be shared
+    /// without the display and is declared for the display only
+    Clipboard,
+    /// Webcams, `/dev/video*` nodes
+    Camera,
+    /// ALSA devices of `/dev/snd`, for programs not supporting sound servers
+    AudioDevices,
+    /// Raw USB devices of `/dev/bus/usb`
+    Usb,
+    /// Joysticks and other input devices of `/dev/input`
+    Input,
+    /// DRI render nodes for GPU acceleration
+    Gpu,
+    /// Host SSH agent, keys stay on the host
+    SshAgent,
+    /// Host gpg-agent for signing and decryption, secret keys stay on the host
+    GpgAgent,
 }
 
 impl Display for Feature {
@@ -57,12 +84,146 @@ impl Display for Feature {
                 Feature::Notification => "Notification",
                 Feature::Devices => "Devices",
                 Feature::HomePersistent => "Home persistent",
+                Feature::HomeSandbox => "Home sandboxed",
                 Feature::Time => "Timezone",
+                Feature::Persistent => "Persistent",
+                Feature::Printing => "Printing",
+                Feature::Portal => "Portal",
+                Feature::Clipboard => "Clipboard",
+                Feature::Camera => "Camera",
+                Feature::AudioDevices => "Audio devices",
+                Feature::Usb => "USB",
+                Feature::Input => "Input",
+                Feature::Gpu => "GPU",
+                Feature::SshAgent => "SSH agent",
+                Feature::GpgAgent => "GPG agent",
             }
         )
     }
 }
 
+impl Feature {
+    /// Class of host devices shared by a device feature
+    pub(crate) fn device_class(&self) -> Option<DeviceClass> {
+        match self {
+            Feature::Camera => Some(DeviceClass::Camera),
+            Feature::AudioDevices => Some(DeviceClass::Audio),
+            Feature::Usb => Some(DeviceClass::Usb),
+            Feature::Input => Some(DeviceClass::Input),
+            Feature::Gpu => Some(DeviceClass::Render),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct Mount {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub read_only: bool,
+}
+
+impl Mount {
+    pub fn new(source: &Path, target: &Path, read_only: bool) -> Self {
+        Mount {
+            source: source.to_owned(),
+            target: target.to_owned(),
+            read_only,
+        }
+    }
+
+    /// Shares a host folder with a container home. Relative paths are resolved against
+    /// the host home directory
+    pub fn share(folder: &Path, read_only: bool) -> AppResult<Self> {
+        let home =
+            dirs::home_dir().ok_or(AppError::File("Can not find a home directory".into()))?;
+        let (source, relative) = get_shared_folder(&home, folder)?;
+
+        Ok(Mount::new(
+            &source,
+            &util::get_container_home()?.join(relative),
+            read_only,
+        ))
+    }
+}
+
+/// Resolved host folder and its path relative to the home. Links and `..` can't lead
+/// outside the home, as the folder is mounted into the container home
+fn get_shared_folder(home: &Path, folder: &Path) -> AppResult<(PathBuf, PathBuf)> {
+    let outside = || {
+        AppError::File(format!(
+            "Shared folder '{}' is not inside a home directory",
+            folder.display()
+        ))
+    };
+
+    if folder.components().any(|c| c == Component::ParentDir) {
+        return Err(outside());
+    }
+
+    let source = std::fs::canonicalize(home.join(folder))
+        .ok()
+        .filter(|source| source.is_dir())
+        .ok_or(AppError::File(format!(
+            "Shared folder '{}' doesn't exist",
+            folder.display()
+        )))?;
+    let home = std::fs::canonicalize(home).map_err(|err| AppError::File(err.to_string()))?;
+    let relative = source
+        .strip_prefix(&home)
+        .map_err(|_| outside())?
+        .to_owned();
+
+    Ok((source, relative))
+}
+
+impl Display for Mount {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}", self.source.display(), self.target.display())?;
+
+        if self.read_only {
+            write!(f, ":ro")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Mount {
+    type Err = AppError;
+
+    /// Parses a mount in docker volume format: `source:target[:ro]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split(':').collect::<Vec<&str>>().as_slice() {
+            [source, target] => Ok(Mount::new(Path::new(source), Path::new(target), false)),
+            [source, target, "ro"] => Ok(Mount::new(Path::new(source), Path::new(target), true)),
+            [source, target, "rw"] => Ok(Mount::new(Path::new(source), Path::new(target), false)),
+            _ => Err(AppError::Program(format!(
+                "Invalid mount '{}', expected 'source:target[:ro]'",
+                s
+            ))),
+        }
+    }
+}
+
+/// Additional resources shared with a program on every run
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct RunOptions {
+    #[serde(default)]
+    pub mounts: Vec<Mount>,
+    #[serde(default)]
+    pub env: Vec<(String, String)>,
+    #[serde(default)]
+    pub devices: Vec<PathBuf>,
+    /// Filter the session bus, so only services of enabled features are reachable
+    #[serde(default)]
+    pub dbus_proxy: bool,
+    /// Pass ozone flags to an Electron program in wayland sessions, Electron before 28
+    /// ignores the ozone environment hint
+    #[serde(default)]
+    pub electron: bool,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Program {
     name: String,
@@ -71,6 +232,11 @@ pub struct Program {
     pub icon: Option<Icon>,
     pub command: String,
     pub deps: Option<String>,
+    #[serde(default)]
+    pub options: RunOptions,
+    /// Package version of the installed build, used to check updates
+    #[serde(default)]
+    pub version: Option<String>,
 }
 
 impl Program {
@@ -89,6 +255,7 @@ impl Program {
         icon: &Option<Icon>,
         cmd: &Option<String>,
         deps: &Option<String>,
+        options: &RunOptions,
     ) -> Self
     where
         T: Into<String>,
@@ -102,6 +269,8 @@ impl Program {
             icon: icon.to_owned(),
             command: cmd.to_owned().unwrap_or(name),
             deps: deps.to_owned(),
+            options: options.to_owned(),
+            version: None,
         }
     }
 }
@@ -109,6 +278,30 @@ impl Program {
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Config {
     pub programs: Vec<Program>,
+    /// Number of image builds kept per program for rollbacks
+    #[serde(default = "default_keep_builds")]
+    pub keep_builds: usize,
+    /// Container engine used to build and run programs
+    #[serde(default)]
+    pub engine: EngineKind,
+    /// Docker daemon address
+    #[serde(default)]
+    pub docker: Endpoint,
+}
+
+fn default_keep_builds() -> usize {
+    KEEP_BUILDS_DEFAULT
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            programs: vec![],
+            keep_builds: KEEP_BUILDS_DEFAULT,
+            engine: EngineKind::default(),
+            docker: Endpoint::default(),
+        }
+    }
 }
 
 impl Config {
@@ -116,7 +309,7 @@ impl Config {
         if !path.exists() {
             return File::create(path)
                 .map(|_| {
-                    let config = Config { programs: vec![] };
+                    let config = Config::default();
 
                     config.serialize(path);
 
@@ -134,7 +327,7 @@ impl Config {
             .map_err(|err| AppError::File(err.to_string()))?;
 
         if config_str.is_empty() {
-            return Ok(Config { programs: vec![] });
+            return Ok(Config::default());
         }
 
         serde_json::from_str(config_str.as_str()).map_err(|err| AppError::File(err.to_string()))
@@ -185,8 +378,61 @@ impl Config {
         Ok(self)
     }
 
+    pub fn update(&mut self, program: &Program) -> AppResult<&Self> {
+        let program_idx = self
+            .find(&program.name)
+            .ok_or(AppError::Program(
+                format!("Can't find a program '{}'", program.name).to_string(),
+            ))?
+            .1;
+
+        self.programs[program_idx] = program.to_owned();
+        Ok(self)
+    }
+
     pub fn clear(&mut self) -> &Self {
         self.programs = vec![];
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mount_parses_success() {
+        let mount = Mount::from_str("/foo:/bar:ro").unwrap();
+
+        assert!(mount == Mount::new(Path::new("/foo"), Path::new("/bar"), true));
+        assert_eq!("/foo:/bar:ro", mount.to_string());
+
+        let mount = Mount::from_str("/foo:/bar").unwrap();
+
+        assert_eq!("/foo:/bar", mount.to_string());
+        assert!(Mount::from_str("/foo").is_err());
+        assert!(Mount::from_str("/foo:/bar:baz").is_err());
+    }
+
+    #[test]
+    fn shared_folder_inside_home_success() {
+        let home = std::env::temp_dir().join("debian_bridge_share_test");
+
+        std::fs::create_dir_all(home.join("Downloads")).unwrap();
+
+        let (source, relative) = get_shared_folder(&home, Path::new("Downloads")).unwrap();
+
+        assert_eq!(
+            std::fs::canonicalize(home.join("Downloads")).unwrap(),
+            source
+        );
+        assert_eq!(Path::new("Downloads"), relative);
+        assert!(get_shared_folder(&home, &home.join("Downloads")).is_ok());
+        assert!(get_shared_folder(&home, Path::new("../..")).is_err());
+        assert!(get_shared_folder(&home, Path::new("Downloads/../..")).is_err());
+        assert!(get_shared_folder(&home, &std::env::temp_dir()).is_err());
+        assert!(get_shared_folder(&home, Path::new("/")).is_err());
+
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+use super::error::AppError;
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+type AppResult<T> = Result<T, AppError>;
+
+pub const CONTEXT_PREFIX: &str = "build-";
+pub const LOGS_DIR: &str = "logs";
+
+/// Unique temporary directory used as an image build context.
+/// The directory is removed when the context is dropped, including on error paths
+pub struct BuildContext {
+    path: PathBuf,
+}
+
+impl BuildContext {
+    pub fn try_new(cache_path: &Path) -> AppResult<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let mut path = cache_path.to_owned();
+
+        path.push(format!(
+            "{}{}-{}",
+            CONTEXT_PREFIX,
+            std::process::id(),
+            nanos
+        ));
+
+        std::fs::create_dir_all(cache_path).map_err(|err| AppError::File(err.to_string()))?;
+        std::fs::create_dir(&path).map_err(|err| AppError::File(err.to_string()))?;
+
+        debug!("Build context: {}", path.display());
+
+        Ok(BuildContext { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    pub fn copy<T: Into<String>>(&self, source: &Path, name: T) -> AppResult<&Self> {
+        std::fs::copy(source, self.path.join(name.into()))
+            .map(|_| self)
+            .map_err(|err| AppError::File(err.to_string()))
+    }
+
+    pub fn write<T: Into<String>, S: Into<String>>(&self, name: T, content: S) -> AppResult<&Self> {
+        std::fs::write(self.path.join(name.into()), content.into())
+            .map(|_| self)
+            .map_err(|err| AppError::File(err.to_string()))
+    }
+}
+
+impl Drop for BuildContext {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.path).unwrap_or_else(|err| {
+            warn!(
+                "Can't remove a build context '{}': {}",
+                self.path.display(),
+                err.to_string()
+            );
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_removed_on_drop() {
+        let mut cache_path = std::env::temp_dir();
+        cache_path.push("debian_bridge_context_test");
+
+        let first = BuildContext::try_new(&cache_path).unwrap();
+        let second = BuildContext::try_new(&cache_path).unwrap();
+        let path = first.path().to_owned();
+
+        first.write("Dockerfile", "FROM debian:9-slim").unwrap();
+
+        assert_ne!(first.path(), second.path());
+        assert!(path.join("Dockerfile").exists());
+
+        drop(first);
+
+        assert!(!path.exists());
+        assert!(second.path().exists());
+
+        drop(second);
+        std::fs::remove_dir(&cache_path).unwrap();
+    }
+}
@@ -0,0 +1,136 @@
+use super::{error::AppError, Feature};
+use std::{
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+type AppResult<T> = Result<T, AppError>;
+
+pub const DBUS_PREFIX: &str = "dbus-";
+pub const DBUS_DIR: &str = "dbus";
+pub const PROXY_SOCKET: &str = "bus";
+const NOTIFICATIONS: &str = "org.freedesktop.Notifications";
+const PORTAL: &str = "org.freedesktop.portal.*";
+const PROXY_TIMEOUT_MS: u64 = 3000;
+const PROXY_POLL_MS: u64 = 50;
+
+/// Bus names used by features of a program, the session bus isn't shared without them
+pub fn get_bus_names(settings: &Vec<Feature>) -> Vec<&'static str> {
+    let mut names = vec![];
+
+    if settings.contains(&Feature::Notification) {
+        names.push(NOTIFICATIONS);
+    }
+
+    if settings.contains(&Feature::Portal) {
+        names.push(PORTAL);
+    }
+
+    names
+}
+
+/// Address of the session bus from `DBUS_SESSION_BUS_ADDRESS`
+pub fn get_session_bus() -> Option<String> {
+    std::env::var("DBUS_SESSION_BUS_ADDRESS")
+        .ok()
+        .filter(|address| !address.is_empty())
+}
+
+/// Socket path of the first `unix:path=` address. Abstract sockets don't have one, they are
+/// reachable from containers sharing the host network
+pub fn get_socket_path(address: &str) -> Option<PathBuf> {
+    address
+        .split(';')
+        .filter(|address| address.starts_with("unix:"))
+        .flat_map(|address| address["unix:".len()..].split(','))
+        .find(|param| param.starts_with("path="))
+        .map(|param| PathBuf::from(&param["path=".len()..]))
+}
+
+/// Session bus proxy letting programs talk to the given bus names only.
+/// The proxy is stopped and its socket removed when dropped
+pub struct DbusProxy {
+    dir: PathBuf,
+    child: Child,
+}
+
+impl DbusProxy {
+    pub fn try_new(dir: &Path, address: &str, names: &Vec<&str>) -> AppResult<Self> {
+        let socket = dir.join(PROXY_SOCKET);
+
+        std::fs::create_dir_all(dir).map_err(|err| AppError::File(err.to_string()))?;
+
+        if socket.exists() {
+            std::fs::remove_file(&socket).map_err(|err| AppError::File(err.to_string()))?;
+        }
+
+        let child = Command::new("xdg-dbus-proxy")
+            .arg(address)
+            .arg(&socket)
+            .arg("--filter")
+            .args(names.iter().map(|name| format!("--talk={}", name)))
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|err| AppError::Program(format!("Can not run xdg-dbus-proxy: {}", err)))?;
+        let mut proxy = DbusProxy {
+            dir: dir.to_owned(),
+            child,
+        };
+        let mut waited = 0;
+
+        while !socket.exists() {
+            if waited >= PROXY_TIMEOUT_MS
+                || proxy
+                    .child
+                    .try_wait()
+                    .map_or(false, |status| status.is_some())
+            {
+                return Err(AppError::Program(
+                    "Session bus proxy didn't start".to_string(),
+                ));
+            }
+
+            thread::sleep(Duration::from_millis(PROXY_POLL_MS));
+            waited += PROXY_POLL_MS;
+        }
+
+        debug!("Session bus proxy: {}", socket.display());
+
+        Ok(proxy)
+    }
+
+    pub fn dir(&self) -> &Path {
+        self.dir.as_path()
+    }
+}
+
+impl Drop for DbusProxy {
+    fn drop(&mut self) {
+        self.child.kill().unwrap_or_default();
+        self.child.wait().map(|_| ()).unwrap_or_default();
+
+        std::fs::remove_file(self.dir.join(PROXY_SOCKET)).unwrap_or_default();
+        std::fs::remove_dir(&self.dir).unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn socket_path_parses_success() {
+        assert_eq!(
+            Some(PathBuf::from("/run/user/1000/bus")),
+            get_socket_path("unix:path=/run/user/1000/bus")
+        );
+        assert_eq!(
+            Some(PathBuf::from("/tmp/dbus-1")),
+            get_socket_path("unix:abstract=/tmp/dbus-0,guid=1;unix:guid=2,path=/tmp/dbus-1")
+        );
+        assert_eq!(None, get_socket_path("unix:abstract=/tmp/dbus-0,guid=1"));
+        assert_eq!(None, get_socket_path("tcp:host=localhost,port=1"));
+    }
+}
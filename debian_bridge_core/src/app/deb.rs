@@ -4,7 +4,14 @@ use colorful::core::StrMarker;
 use mocktopus::macros::*;
 use pipers::Pipe;
 use regex::Regex;
-use std::{convert::TryInto, ffi::OsStr, path::Path};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    convert::TryInto,
+    ffi::OsStr,
+    fmt::{Display, Formatter},
+    path::Path,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Dependencies {
@@ -109,6 +116,135 @@ impl Deb {
     }
 }
 
+/// Difference of an installed package between two image builds
+#[derive(Debug, Clone, PartialEq)]
+pub enum PackageChange {
+    Added(String, String),
+    Removed(String, String),
+    Changed(String, String, String),
+}
+
+impl PackageChange {
+    /// Lists changed packages between two `package => version` sets sorted by name
+    pub fn diff(before: &HashMap<String, String>, after: &HashMap<String, String>) -> Vec<Self> {
+        let mut changes: Vec<Self> = after
+            .iter()
+            .filter_map(|(package, version)| match before.get(package) {
+                None => Some(PackageChange::Added(package.to_owned(), version.to_owned())),
+                Some(old) if old != version => Some(PackageChange::Changed(
+                    package.to_owned(),
+                    old.to_owned(),
+                    version.to_owned(),
+                )),
+                _ => None,
+            })
+            .chain(
+                before
+                    .iter()
+                    .filter(|(package, _)| !after.contains_key(*package))
+                    .map(|(package, version)| {
+                        PackageChange::Removed(package.to_owned(), version.to_owned())
+                    }),
+            )
+            .collect();
+
+        changes.sort_by(|a, b| a.package().cmp(b.package()));
+        changes
+    }
+
+    pub fn package(&self) -> &String {
+        match self {
+            PackageChange::Added(package, _)
+            | PackageChange::Removed(package, _)
+            | PackageChange::Changed(package, _, _) => package,
+        }
+    }
+}
+
+impl Display for PackageChange {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            PackageChange::Added(package, version) => write!(f, "+ {} {}", package, version),
+            PackageChange::Removed(package, version) => write!(f, "- {} {}", package, version),
+            PackageChange::Changed(package, old, new) => {
+                write!(f, "* {} {} -> {}", package, old, new)
+            }
+        }
+    }
+}
+
+/// Compares two Debian package versions following the dpkg ordering rules
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a_epoch, a_upstream, a_revision) = split_version(a);
+    let (b_epoch, b_upstream, b_revision) = split_version(b);
+
+    a_epoch
+        .cmp(&b_epoch)
+        .then_with(|| compare_parts(a_upstream, b_upstream))
+        .then_with(|| compare_parts(a_revision, b_revision))
+}
+
+fn split_version(version: &str) -> (u64, &str, &str) {
+    let version = version.trim();
+    let (epoch, rest) = match version.find(':') {
+        Some(idx) => (version[..idx].parse().unwrap_or(0), &version[idx + 1..]),
+        None => (0, version),
+    };
+
+    match rest.rfind('-') {
+        Some(idx) => (epoch, &rest[..idx], &rest[idx + 1..]),
+        None => (epoch, rest, ""),
+    }
+}
+
+fn char_order(c: Option<u8>) -> i32 {
+    match c {
+        Some(b'~') => -1,
+        Some(c) if c.is_ascii_digit() => 0,
+        Some(c) if c.is_ascii_alphabetic() => c as i32,
+        Some(c) => c as i32 + 256,
+        None => 0,
+    }
+}
+
+fn compare_parts(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+
+    while !a.is_empty() || !b.is_empty() {
+        while a.first().map_or(false, |c| !c.is_ascii_digit())
+            || b.first().map_or(false, |c| !c.is_ascii_digit())
+        {
+            let order = char_order(a.first().cloned()).cmp(&char_order(b.first().cloned()));
+
+            if order != Ordering::Equal {
+                return order;
+            }
+
+            a = a.get(1..).unwrap_or_default();
+            b = b.get(1..).unwrap_or_default();
+        }
+
+        let a_len = a.iter().take_while(|c| c.is_ascii_digit()).count();
+        let b_len = b.iter().take_while(|c| c.is_ascii_digit()).count();
+        let a_num = std::str::from_utf8(&a[..a_len])
+            .unwrap()
+            .trim_start_matches('0');
+        let b_num = std::str::from_utf8(&b[..b_len])
+            .unwrap()
+            .trim_start_matches('0');
+        let order = a_num.len().cmp(&b_num.len()).then_with(|| a_num.cmp(b_num));
+
+        if order != Ordering::Equal {
+            return order;
+        }
+
+        a = &a[a_len..];
+        b = &b[b_len..];
+    }
+
+    Ordering::Equal
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -132,4 +268,47 @@ mod tests {
 
         assert_eq!("one_dep", deps.extract());
     }
+
+    #[test]
+    fn packages_diff_success() {
+        let before: HashMap<String, String> = vec![
+            ("libc6".to_string(), "2.24-11".to_string()),
+            ("libssl1.1".to_string(), "1.1.0j-1".to_string()),
+            ("openssl".to_string(), "1.1.0j-1".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let after: HashMap<String, String> = vec![
+            ("curl".to_string(), "7.52.1-5".to_string()),
+            ("libc6".to_string(), "2.24-11".to_string()),
+            ("libssl1.1".to_string(), "1.1.0l-1".to_string()),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            vec![
+                PackageChange::Added("curl".to_string(), "7.52.1-5".to_string()),
+                PackageChange::Changed(
+                    "libssl1.1".to_string(),
+                    "1.1.0j-1".to_string(),
+                    "1.1.0l-1".to_string()
+                ),
+                PackageChange::Removed("openssl".to_string(), "1.1.0j-1".to_string()),
+            ],
+            PackageChange::diff(&before, &after)
+        );
+    }
+
+    #[test]
+    fn versions_compare_success() {
+        assert_eq!(Ordering::Less, compare_versions("1.0.2", "1.0.10"));
+        assert_eq!(Ordering::Equal, compare_versions("1.0", "1.00"));
+        assert_eq!(Ordering::Less, compare_versions("1.0~rc1", "1.0"));
+        assert_eq!(Ordering::Greater, compare_versions("1.0a", "1.0"));
+        assert_eq!(Ordering::Greater, compare_versions("1:0.9", "2.0"));
+        assert_eq!(Ordering::Less, compare_versions("2.0-1", "2.0-2"));
+        assert_eq!(Ordering::Greater, compare_versions("2.0-1ubuntu1", "2.0-1"));
+        assert_eq!(Ordering::Less, compare_versions("1.2.3-1", "1.2.3.1-1"));
+    }
 }
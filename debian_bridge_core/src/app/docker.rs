@@ -1,213 +1,973 @@
-use super::{error::AppError, Feature, Program, System};
-use colorful::core::StrMarker;
-use serde_json::Value;
-use shiplift::{BuildOptions, ContainerListOptions, Docker};
+use super::{
+    context::LOGS_DIR,
+    dbus::{self, DbusProxy, DBUS_DIR, DBUS_PREFIX, PROXY_SOCKET},
+    error::AppError,
+    gc::{self, Garbage},
+    gnupg::{GnupgHome, AGENT_SOCKET, GNUPG_DIR, GNUPG_PREFIX},
+    labels::Labels,
+    progress::{BuildProgress, ProgressHandler},
+    util,
+    xauth::{XAuthority, XAUTH_DIR, XAUTH_FILE, XAUTH_PREFIX},
+    Feature, Mount, Program, System,
+};
+use crate::{
+    engine::{ContainerInfo, Engine},
+    sys::driver::{DeviceClass, SoundDriver, WindowManager},
+};
 use std::{
+    collections::HashMap,
+    fs::File,
+    io::Write,
     path::{Path, PathBuf},
-    process::{Command, Stdio},
-    sync::{Arc, Mutex},
-};
-use tokio::{
-    prelude::{Future, Stream},
-    runtime::Runtime,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 type AppResult<T> = Result<T, AppError>;
 
+const ACTIVE_TAG: &str = "latest";
+const XAUTH_MOUNT_PATH: &str = "/tmp/.debian_bridge-xauth";
+const PULSE_COOKIE_PATH: &str = "/tmp/.debian_bridge-pulse-cookie";
+const DBUS_MOUNT_PATH: &str = "/tmp/.debian_bridge-dbus";
+const CUPS_SOCKET_PATH: &str = "/run/cups/cups.sock";
+const SSH_AGENT_PATH: &str = "/tmp/.debian_bridge-ssh-agent";
+/// GnuPG home of containers, gpg finds the agent socket in it
+const GNUPG_MOUNT_PATH: &str = "/tmp/.debian_bridge-gnupg";
+
+/// Toolkit hints making GTK, Qt and Electron programs prefer wayland over XWayland
+const WAYLAND_ENV: &[&str] = &[
+    "GDK_BACKEND=wayland,x11",
+    "QT_QPA_PLATFORM=wayland;xcb",
+    "ELECTRON_OZONE_PLATFORM_HINT=wayland",
+];
+const OZONE_FLAGS: &[&str] = &[
+    "--enable-features=UseOzonePlatform",
+    "--ozone-platform=wayland",
+];
+
+/// Host resources shared with a program while it runs
+struct RunResources {
+    xauth: Option<XAuthority>,
+    dbus_proxy: Option<DbusProxy>,
+    gnupg: Option<GnupgHome>,
+}
+
+/// Tagged build of a program image
+#[derive(Clone, Debug)]
+pub struct ImageBuild {
+    pub tag: String,
+    pub created: u64,
+    pub active: bool,
+}
+
 pub struct DockerFacade<'a> {
-    docker: &'a Docker,
+    engine: &'a dyn Engine,
     system: &'a System,
     prefix: String,
     cache_path: PathBuf,
+    data_path: PathBuf,
+    progress: Option<ProgressHandler>,
 }
 
 impl<'a> DockerFacade<'a> {
     pub fn new<T: Into<String>>(
-        docker: &'a Docker,
+        engine: &'a dyn Engine,
         system: &'a System,
         prefix: T,
         cache_path: &Path,
+        data_path: &Path,
     ) -> Self {
         DockerFacade {
-            docker,
+            engine,
             system,
             prefix: prefix.into(),
             cache_path: cache_path.into(),
+            data_path: data_path.into(),
+            progress: None,
         }
     }
 
-    fn get_containers(&self, image: &String) -> AppResult<Vec<String>> {
-        let program_name = Arc::new(image.to_owned());
+    fn get_containers(&self, program: &Program) -> AppResult<Vec<String>> {
+        self.find_containers(true, |c| self.is_program_container(program, c))
+    }
 
-        let fut = self
-            .docker
-            .containers()
-            .list(&ContainerListOptions::builder().all().build())
-            .map(move |containers| {
-                containers
-                    .iter()
-                    .filter_map(|c| {
-                        if c.image.eq(&*program_name) {
-                            Some(c.id.to_owned())
-                        } else {
-                            None
-                        }
-                    })
-                    .collect()
-            })
-            .map_err(|_| ());
+    fn get_running_containers(&self, program: &Program) -> AppResult<Vec<String>> {
+        self.find_containers(false, |c| self.is_program_container(program, c))
+    }
+
+    /// Matches containers by labels. Containers of images built before labelling are matched
+    /// by the image or container name
+    fn is_program_container(&self, program: &Program, c: &ContainerInfo) -> bool {
+        if Labels::is_labelled(&c.labels) {
+            return Labels::get_program(&c.labels, &self.prefix) == Some(program.get_name_short());
+        }
 
-        let mut rt = Runtime::new().unwrap();
+        let name = program.get_name(&self.prefix);
 
-        let container_ids = match rt.block_on(fut) {
-            Ok(res) => Ok(res),
-            Err(err) => Err(AppError::Docker),
-        };
+        c.image == name
+            || c.image.starts_with(&format!("{}:", name))
+            || c.names.contains(&format!("/{}", name))
+    }
 
-        rt.shutdown_now().wait().map_err(|err| AppError::Docker)?;
+    fn get_persistent_container(&self, program: &Program) -> AppResult<Option<String>> {
+        let name = format!("/{}", program.get_name(&self.prefix));
 
-        container_ids
+        self.find_containers(true, move |c| c.names.contains(&name))
+            .map(|ids| ids.first().cloned())
     }
 
-    fn delete_container(&self, id: &String) -> AppResult<&Self> {
-        let fut = self
-            .docker
-            .containers()
-            .get(&id)
-            .delete()
-            .map_err(|_| AppError::Docker);
-        let mut rt = Runtime::new().unwrap();
-
-        rt.block_on(fut).map_err(|err| {
-            warn!("{}", err.to_string());
-            AppError::Docker
-        })?;
-        rt.shutdown_now().wait().map_err(|err| AppError::Docker)?;
+    fn find_containers<P>(&self, all: bool, predicate: P) -> AppResult<Vec<String>>
+    where
+        P: Fn(&ContainerInfo) -> bool,
+    {
+        Ok(self
+            .engine
+            .containers(all)?
+            .iter()
+            .filter(|c| predicate(c))
+            .map(|c| c.id.to_owned())
+            .collect())
+    }
 
-        Ok(self)
+    /// Finds stopped containers and images of programs missing in the configuration, stopped
+    /// containers of non persistent programs and untagged images left by failed builds
+    pub fn find_garbage(&self, programs: &Vec<Program>) -> AppResult<Vec<Garbage>> {
+        let find = |name: &String| programs.iter().find(|p| &p.get_name_short() == name);
+        let mut garbage = vec![];
+
+        for c in self.engine.containers(true)? {
+            let name = match Labels::get_program(&c.labels, &self.prefix) {
+                Some(name) => name,
+                None => continue,
+            };
+            let orphan = match find(&name) {
+                Some(program) => !program.settings.contains(&Feature::Persistent),
+                None => true,
+            };
+
+            if orphan && c.state != "running" {
+                garbage.push(Garbage::Container {
+                    id: c.id.to_owned(),
+                    name: c.names.first().cloned().unwrap_or(c.image.to_owned()),
+                });
+            }
+        }
+
+        for image in self.engine.images()? {
+            let name = match Labels::get_program(&image.labels, &self.prefix) {
+                Some(name) => name,
+                None => continue,
+            };
+
+            if image.tags.is_empty() {
+                garbage.push(Garbage::Image(image.id));
+            } else if find(&name).is_none() {
+                image
+                    .tags
+                    .into_iter()
+                    .for_each(|tag| garbage.push(Garbage::Image(tag)));
+            }
+        }
+
+        Ok(garbage)
+    }
+
+    pub fn remove_garbage(&self, garbage: &Garbage) -> AppResult<&Self> {
+        match garbage {
+            Garbage::Container { id, .. } => self.delete_container(id),
+            Garbage::Image(tag) => self.delete_image(tag),
+            Garbage::File(path) => gc::remove_file(path).map(|_| self),
+        }
+    }
+
+    fn delete_container(&self, id: &String) -> AppResult<&Self> {
+        self.engine.delete_container(id).map(|_| self)
     }
 
     pub fn delete(&mut self, program: &Program) -> AppResult<&Self> {
         let name = program.get_name(&self.prefix);
-        let containers_ids = self.get_containers(&name)?;
+        let containers_ids = self.get_containers(&program)?;
 
         containers_ids
             .iter()
             .try_for_each(|id| self.delete_container(&id).map(|_| ()))?;
 
-        let fut = self.docker.images().get(&name).delete();
-        let mut rt = Runtime::new().unwrap();
+        self.get_builds(&program.get_name_short())?
+            .iter()
+            .try_for_each(|build| {
+                self.delete_image(&format!("{}:{}", name, build.tag))
+                    .map(|_| ())
+            })?;
+
+        self.delete_image(&name)
+    }
+
+    fn delete_image(&self, name: &String) -> AppResult<&Self> {
+        self.engine.delete_image(name).map(|_| self)
+    }
+
+    /// Lists tagged builds of a program image, newest first
+    pub fn get_builds(&self, name: &String) -> AppResult<Vec<ImageBuild>> {
+        let prefix = format!("{}_{}:", self.prefix, name);
+        let active = format!("{}{}", prefix, ACTIVE_TAG);
+        let images = self.engine.images()?;
+        let active_id = images
+            .iter()
+            .find(|image| image.tags.contains(&active))
+            .map(|image| image.id.to_owned());
+        let mut builds: Vec<ImageBuild> = images
+            .iter()
+            .filter(|image| Labels::get_program(&image.labels, &self.prefix).as_ref() == Some(name))
+            .flat_map(|image| {
+                let is_active = active_id.as_ref() == Some(&image.id);
+
+                image
+                    .tags
+                    .iter()
+                    .filter(|tag| tag.starts_with(&prefix))
+                    .filter_map(|tag| {
+                        let tag = tag[prefix.len()..].to_string();
+                        let (_, created) = util::parse_image_tag(&tag)?;
+
+                        Some(ImageBuild {
+                            tag,
+                            created,
+                            active: is_active,
+                        })
+                    })
+                    .collect::<Vec<ImageBuild>>()
+            })
+            .collect();
+
+        builds.sort_by(|a, b| b.created.cmp(&a.created));
+        Ok(builds)
+    }
 
-        rt.block_on(fut).map_err(|err| {
-            warn!("{}", err.to_string());
-            AppError::DockerStatus(404)
-        })?;
-        rt.shutdown_now().wait().map_err(|err| AppError::Docker)?;
+    /// Makes a previous build (or the newest build of a given version) active
+    pub fn rollback(&mut self, program: &Program, version: &Option<String>) -> AppResult<&Self> {
+        let name = program.get_name(&self.prefix);
+        let builds = self.get_builds(&program.get_name_short())?;
+        let build = match version {
+            Some(version) => {
+                let version = util::sanitize_tag_version(&version);
+
+                builds.iter().find(|build| {
+                    util::parse_image_tag(&build.tag).map_or(false, |(v, _)| v == version)
+                })
+            }
+            None => builds
+                .iter()
+                .position(|build| build.active)
+                .and_then(|idx| builds.get(idx + 1)),
+        }
+        .ok_or(AppError::Program(format!(
+            "Can not find a build to roll back to, available builds: {}",
+            builds
+                .iter()
+                .map(|build| build.tag.to_owned())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )))?;
+
+        self.engine.tag(
+            &format!("{}:{}", name, build.tag),
+            &format!("{}:{}", name, ACTIVE_TAG),
+        )?;
+
+        info!(
+            "Active build of '{}' is '{}'",
+            program.get_name_short(),
+            build.tag
+        );
+
+        if program.settings.contains(&Feature::Persistent) {
+            info!("Reset a persistent container to start it from the active build");
+        }
 
         Ok(self)
     }
 
-    pub fn create<T: Into<String>>(&mut self, name: T) -> AppResult<&Self> {
-        let tag = format!("{}_{}", self.prefix, name.into());
+    /// Removes the oldest builds except the active one, keeping `keep` builds in total
+    fn prune(&self, name: &String, keep: usize) -> AppResult<&Self> {
+        let image = format!("{}_{}", self.prefix, name);
 
-        info!("Image name: {}", tag);
+        self.get_builds(&name)?
+            .iter()
+            .filter(|build| !build.active)
+            .skip(keep.max(1) - 1)
+            .try_for_each(|build| {
+                info!("Removing an old build '{}'", build.tag);
+                self.delete_image(&format!("{}:{}", image, build.tag))
+                    .map(|_| ())
+            })?;
 
-        let fut = self
-            .docker
-            .images()
-            .build(
-                &BuildOptions::builder(self.cache_path.as_os_str().to_str().unwrap())
-                    .tag(&tag)
-                    .build(),
-            )
-            .for_each(|output| {
-                let output = output.as_object().unwrap();
-
-                if output.contains_key("error") {
-                    error!("Docker output: {}", output.get("error").unwrap());
-                    return Err(shiplift::Error::InvalidResponse(
-                        "Failed to build an image".to_string(),
-                    ));
-                }
+        Ok(self)
+    }
 
-                if output.contains_key("stream") {
-                    info!("Docker output: {}", output.get("stream").unwrap());
-                }
+    pub fn reset(&mut self, program: &Program) -> AppResult<&Self> {
+        match self.get_persistent_container(&program)? {
+            Some(id) => self.delete_container(&id),
+            None => {
+                info!("No persistent container found, nothing to reset");
+                Ok(self)
+            }
+        }
+    }
+
+    pub fn create<T: Into<String>>(
+        &mut self,
+        name: T,
+        version: &Option<String>,
+        context: &Path,
+        keep: usize,
+        refresh: bool,
+    ) -> AppResult<&Self> {
+        let name = name.into();
+        let image = format!("{}_{}", self.prefix, name);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let tag = format!("{}:{}", image, util::gen_image_tag(&version, timestamp));
+        let (mut log, log_path) = self.create_log(&name)?;
+        let progress = self.progress.clone();
 
-                Ok(())
+        info!("Image name: {}", tag);
+        info!("Build log: {}", log_path.display());
+
+        if refresh {
+            info!("Pulling a base image '{}'", util::BASE_IMAGE);
+            self.engine.pull(util::BASE_IMAGE)?;
+        }
+
+        let output = Box::new(move |output: &serde_json::Value| {
+            writeln!(log, "{}", output.to_string()).unwrap_or_default();
+
+            if let Some(error) = output.get("error") {
+                error!("Docker output: {}", error);
+                return Err(AppError::Program("Failed to build an image".to_string()));
+            }
+
+            let progress_event = match BuildProgress::parse(&output) {
+                Some(progress_event) => progress_event,
+                None => return Ok(()),
+            };
+
+            match &progress {
+                Some(handler) => handler(&progress_event),
+                None => match progress_event {
+                    BuildProgress::Step { .. } => info!("{}", progress_event),
+                    _ => debug!("{}", progress_event),
+                },
+            }
+
+            Ok(())
+        });
+
+        self.engine
+            .build(context, &tag, refresh, output)
+            .map_err(|err| {
+                error!("See build log for details: {}", log_path.display());
+                err
+            })?;
+
+        if let Err(err) = self.engine.tag(&tag, &format!("{}:{}", image, ACTIVE_TAG)) {
+            self.delete_image(&tag).unwrap_or_else(|err| {
+                warn!("Can't remove a build '{}': {}", tag, err.to_string());
+                self
             });
-        let mut rt = Runtime::new().unwrap();
+            return Err(err);
+        }
+
+        self.prune(&name, keep)?;
+
+        Ok(self)
+    }
+
+    /// Saves the active image of a program to a tarball
+    pub fn export(&self, program: &Program, path: &Path) -> AppResult<&Self> {
+        let image = format!("{}:{}", program.get_name(&self.prefix), ACTIVE_TAG);
+
+        info!("Exporting an image '{}'", image);
+
+        self.engine.export(&image, path).map(|_| self)
+    }
+
+    /// Loads a program image from a tarball made by `export` and makes it active
+    pub fn import(&self, program: &Program, path: &Path) -> AppResult<&Self> {
+        let image = program.get_name(&self.prefix);
 
-        rt.block_on(fut).map_err(|err| {
-            error!("{}", err.to_string());
-            AppError::Docker
-        })?;
-        rt.shutdown_now().wait().map_err(|err| AppError::Docker)?;
+        info!("Importing an image '{}'", image);
+
+        self.engine.import(path)?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        self.engine.tag(
+            &format!("{}:{}", image, ACTIVE_TAG),
+            &format!(
+                "{}:{}",
+                image,
+                util::gen_image_tag(&program.version, timestamp)
+            ),
+        )?;
 
         Ok(self)
     }
 
+    /// Lists packages installed in the active image of a program with their versions
+    pub fn get_packages(&self, program: &Program) -> AppResult<HashMap<String, String>> {
+        let output = self.engine.output(
+            &vec![
+                "run",
+                "--rm",
+                "--entrypoint",
+                "dpkg-query",
+                &format!("{}:{}", program.get_name(&self.prefix), ACTIVE_TAG),
+                "-W",
+                "-f=${Package}\\t${Version}\\n",
+            ]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect(),
+        )?;
+
+        if !output.status.success() {
+            return Err(AppError::Program(format!(
+                "Can not list packages of '{}'",
+                program.get_name_short()
+            )));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(2, '\t');
+
+                Some((parts.next()?.to_string(), parts.next()?.to_string()))
+            })
+            .collect())
+    }
+
+    pub fn set_progress_handler(&mut self, handler: ProgressHandler) -> &Self {
+        self.progress = Some(handler);
+        self
+    }
+
+    fn create_log(&self, name: &String) -> AppResult<(File, PathBuf)> {
+        let mut path = self.cache_path.to_owned();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        path.push(LOGS_DIR);
+        std::fs::create_dir_all(&path).map_err(|err| AppError::File(err.to_string()))?;
+        path.push(format!("{}-{}.log", name, timestamp));
+
+        File::create(&path)
+            .map(|file| (file, path))
+            .map_err(|err| AppError::File(err.to_string()))
+    }
+
     //TODO: add more options and rewrite with docker API if possible
-    pub fn run(&self, program: &Program) -> AppResult<&Self> {
-        let home = std::env::var_os("HOME")
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string();
-        let cmd_name = program.get_name(&self.prefix);
-        let home_volume = format!("{}:{}", home, home);
-        let mut args = vec![
-            "run",
-            "-ti",
-            "--net=host",
-            "--rm",
-            "-v",
-            "/dev/shm:/dev/shm",
-            "-v",
-            "/etc/machine-id:/etc/machine-id",
-            "-v",
-            "/var/lib/dbus:/var/lib/dbus",
-            "--privileged",
+    pub fn run(&self, program: &Program, arguments: &Vec<String>) -> AppResult<&Self> {
+        let resources = self.get_run_resources(&program)?;
+        let mut args = self.get_run_args(&program, &resources)?;
+
+        if program.settings.contains(&Feature::Persistent) {
+            if let Some(id) = self.get_persistent_container(&program)? {
+                if !arguments.is_empty() {
+                    warn!("Arguments are ignored when an existed persistent container is started");
+                }
+
+                return self.spawn(vec!["start".to_string(), "-ai".to_string(), id]);
+            }
+
+            args.push("--name".to_string());
+            args.push(program.get_name(&self.prefix));
+        } else {
+            args.push("--rm".to_string());
+        }
+
+        let arguments: Vec<String> = self
+            .get_program_flags(&program)
+            .into_iter()
+            .chain(arguments.iter().cloned())
+            .collect();
+
+        if arguments.is_empty() {
+            args.push(program.get_name(&self.prefix));
+            return self.spawn(args);
+        }
+
+        let (arguments, volumes) = util::forward_arguments(&arguments);
+
+        volumes.iter().for_each(|volume| {
+            args.push_volume(volume.as_str());
+        });
+        args.push(program.get_name(&self.prefix));
+        args.push("/bin/sh".to_string());
+        args.push("-c".to_string());
+        args.push(format!("{} \"$@\"", program.command));
+        args.push("sh".to_string());
+        args.extend(arguments);
+
+        self.spawn(args)
+    }
+
+    pub fn shell(&self, program: &Program) -> AppResult<&Self> {
+        let resources = self.get_run_resources(&program)?;
+        let mut args = self.get_run_args(&program, &resources)?;
+
+        args.push("--rm".to_string());
+        args.push("--entrypoint".to_string());
+        args.push("/bin/bash".to_string());
+        args.push(program.get_name(&self.prefix));
+
+        self.spawn(args)
+    }
+
+    pub fn exec(&self, program: &Program, command: &Vec<String>) -> AppResult<&Self> {
+        let container_ids = self.get_running_containers(&program)?;
+        let id = container_ids.first().ok_or(AppError::Program(format!(
+            "Program '{}' is not running",
+            program.get_name_short()
+        )))?;
+
+        if container_ids.len() > 1 {
+            info!("Several instances are running, using '{}'", id);
+        }
+
+        let mut args = vec!["exec".to_string(), "-ti".to_string(), id.to_owned()];
+
+        args.extend(command.iter().cloned());
+
+        self.spawn(args)
+    }
+
+    fn get_sandbox_home(&self, program: &Program) -> PathBuf {
+        let mut path = self.data_path.to_owned();
+
+        path.push(program.get_name_short());
+        path.push("home");
+        path
+    }
+
+    fn get_feature_mounts(&self, program: &Program) -> AppResult<Vec<Mount>> {
+        let home =
+            dirs::home_dir().ok_or(AppError::File("Can not find a home directory".into()))?;
+        let mut mounts = vec![
+            bind("/dev/shm"),
+            bind("/etc/machine-id"),
+            bind("/var/lib/dbus"),
         ];
 
         if program.settings.contains(&Feature::Display) {
-            args.push_volume("/tmp/.X11-unix:/tmp/.X11-unix")
-                .push_env("DISPLAY");
+            mounts.push(bind("/tmp/.X11-unix"));
+
+            if let Some(socket) = self.get_wayland_socket() {
+                mounts.push(Mount::new(&socket, &socket, false));
+            }
         }
 
         if program.settings.contains(&Feature::Sound) {
-            args.push_volume("/dev/snd:/dev/snd");
+            let sockets = self.get_sound_sockets();
+
+            sockets
+                .iter()
+                .for_each(|socket| mounts.push(Mount::new(socket, socket, false)));
+
+            if let Some(cookie) = self
+                .system
+                .pulse_cookie
+                .as_ref()
+                .filter(|_| !sockets.is_empty())
+            {
+                mounts.push(Mount::new(cookie, Path::new(PULSE_COOKIE_PATH), true));
+            }
         }
 
         if program.settings.contains(&Feature::HomePersistent) {
-            args.push_volume(&home_volume);
+            mounts.push(Mount::new(&home, &home, false));
+        }
+
+        if program.settings.contains(&Feature::HomeSandbox) {
+            mounts.push(Mount::new(
+                &self.get_sandbox_home(&program),
+                &util::get_container_home()?,
+                false,
+            ));
         }
 
         if program.settings.contains(&Feature::Time) {
-            args.push_volume("/etc/localtime:/etc/localtime");
+            mounts.push(bind("/etc/localtime"));
+        }
+
+        if !dbus::get_bus_names(&program.settings).is_empty() && !program.options.dbus_proxy {
+            if let Some(socket) = dbus::get_session_bus().and_then(|a| dbus::get_socket_path(&a)) {
+                mounts.push(Mount::new(&socket, &socket, false));
+            }
+        }
+
+        if program.settings.contains(&Feature::Printing) {
+            if let Some(socket) = &self.system.cups {
+                mounts.push(Mount::new(socket, Path::new(CUPS_SOCKET_PATH), false));
+            }
+        }
+
+        if program.settings.contains(&Feature::SshAgent) {
+            if let Some(socket) = &self.system.ssh_agent {
+                mounts.push(Mount::new(socket, Path::new(SSH_AGENT_PATH), true));
+            }
+        }
+
+        if program.settings.contains(&Feature::Devices) {
+            mounts.push(bind("/dev"));
+        }
+
+        Ok(mounts)
+    }
+
+    fn get_feature_env(&self, program: &Program) -> Vec<String> {
+        let mut env = vec![];
+
+        if program.settings.contains(&Feature::Display) {
+            env.push("DISPLAY".to_string());
+
+            if let Some(socket) = self.get_wayland_socket() {
+                let name = socket.file_name().unwrap_or_default().to_string_lossy();
+                let dir = socket.parent().unwrap_or(Path::new("/"));
+
+                env.push(format!("WAYLAND_DISPLAY={}", name));
+                env.push(format!("XDG_RUNTIME_DIR={}", dir.display()));
+                env.extend(WAYLAND_ENV.iter().map(|hint| hint.to_string()));
+            }
+        }
+
+        if program.settings.contains(&Feature::Sound) {
+            if let Some(socket) = self.get_pulse_socket() {
+                env.push(format!("PULSE_SERVER=unix:{}", socket.display()));
+
+                if self.system.pulse_cookie.is_some() {
+                    env.push(format!("PULSE_COOKIE={}", PULSE_COOKIE_PATH));
+                }
+            }
+
+            if let Some(socket) = self.get_pipewire_socket() {
+                env.push(format!("PIPEWIRE_REMOTE={}", socket.display()));
+            }
+        }
+
+        if !dbus::get_bus_names(&program.settings).is_empty() && !program.options.dbus_proxy {
+            if let Some(address) = dbus::get_session_bus() {
+                env.push(format!("DBUS_SESSION_BUS_ADDRESS={}", address));
+            }
+        }
+
+        if program.settings.contains(&Feature::Portal) {
+            env.push("GTK_USE_PORTAL=1".to_string());
+        }
+
+        if program.settings.contains(&Feature::Printing) && self.system.cups.is_some() {
+            env.push(format!("CUPS_SERVER={}", CUPS_SOCKET_PATH));
+        }
+
+        if program.settings.contains(&Feature::SshAgent) && self.system.ssh_agent.is_some() {
+            env.push(format!("SSH_AUTH_SOCK={}", SSH_AGENT_PATH));
+        }
+
+        env
+    }
+
+    /// Flags passed to a program before user arguments
+    fn get_program_flags(&self, program: &Program) -> Vec<String> {
+        let wayland =
+            program.settings.contains(&Feature::Display) && self.get_wayland_socket().is_some();
+
+        match program.options.electron && wayland {
+            true => OZONE_FLAGS.iter().map(|flag| flag.to_string()).collect(),
+            false => vec![],
+        }
+    }
+
+    /// Sound server sockets shared with programs, the sound device is shared without them
+    fn get_sound_sockets(&self) -> Vec<PathBuf> {
+        self.get_pulse_socket()
+            .into_iter()
+            .chain(self.get_pipewire_socket())
+            .collect()
+    }
+
+    /// PulseAudio socket, pipewire-pulse serves it in PipeWire sessions
+    fn get_pulse_socket(&self) -> Option<PathBuf> {
+        match self.system.sd {
+            Some(SoundDriver::PulseAudio) | Some(SoundDriver::PipeWire) => {
+                self.system.pulse.to_owned()
+            }
+            _ => None,
+        }
+    }
+
+    fn get_pipewire_socket(&self) -> Option<PathBuf> {
+        match self.system.sd {
+            Some(SoundDriver::PipeWire) => self.system.pipewire.to_owned(),
+            _ => None,
+        }
+    }
+
+    /// Classes of devices shared by features of a program. Sound falls back to ALSA devices
+    /// without a sound server socket
+    fn get_device_classes(&self, program: &Program) -> Vec<DeviceClass> {
+        let mut classes: Vec<DeviceClass> = program
+            .settings
+            .iter()
+            .filter_map(|feature| feature.device_class())
+            .collect();
+
+        if program.settings.contains(&Feature::Sound)
+            && self.get_sound_sockets().is_empty()
+            && !classes.contains(&DeviceClass::Audio)
+        {
+            classes.push(DeviceClass::Audio);
+        }
+
+        classes
+    }
+
+    fn get_run_resources(&self, program: &Program) -> AppResult<RunResources> {
+        Ok(RunResources {
+            xauth: self.get_xauthority(&program),
+            dbus_proxy: self.get_dbus_proxy(&program)?,
+            gnupg: self.get_gnupg_home(&program)?,
+        })
+    }
+
+    /// Directory of a resource mounted for a run. Persistent containers keep a directory
+    /// per program, as they are started again with the same mounts
+    fn get_run_dir(&self, program: &Program, dir: &str, prefix: &str) -> PathBuf {
+        match program.settings.contains(&Feature::Persistent) {
+            true => {
+                let dir = self.cache_path.join(dir).join(program.get_name_short());
+
+                // a persistent container can't start without its mount source
+                std::fs::create_dir_all(&dir).unwrap_or_default();
+                dir
+            }
+            false => self
+                .cache_path
+                .join(format!("{}{}", prefix, std::process::id())),
+        }
+    }
+
+    /// Xauthority with a cookie of the X11 display. Programs fall back to an access granted
+    /// with `xhost` when there is no cookie
+    fn get_xauthority(&self, program: &Program) -> Option<XAuthority> {
+        if !program.settings.contains(&Feature::Display) {
+            return None;
+        }
+
+        let display = std::env::var("DISPLAY").ok().filter(|d| !d.is_empty())?;
+
+        XAuthority::try_new(
+            &self.get_run_dir(&program, XAUTH_DIR, XAUTH_PREFIX),
+            &display,
+        )
+        .map_err(|err| warn!("X11 cookie is not shared: {}", err.to_string()))
+        .ok()
+    }
+
+    /// Session bus proxy filtering services used by a program. A program isn't started
+    /// when the proxy fails, so it never gets the whole bus
+    fn get_dbus_proxy(&self, program: &Program) -> AppResult<Option<DbusProxy>> {
+        let names = dbus::get_bus_names(&program.settings);
+
+        if names.is_empty() || !program.options.dbus_proxy {
+            return Ok(None);
+        }
+
+        let address = dbus::get_session_bus()
+            .ok_or(AppError::Program("Can not find a session bus".to_string()))?;
+
+        DbusProxy::try_new(
+            &self.get_run_dir(&program, DBUS_DIR, DBUS_PREFIX),
+            &address,
+            &names,
+        )
+        .map(Some)
+    }
+
+    /// GnuPG home with the host public keyring, the agent socket is mounted into it
+    fn get_gnupg_home(&self, program: &Program) -> AppResult<Option<GnupgHome>> {
+        if !program.settings.contains(&Feature::GpgAgent) || self.system.gpg_agent.is_none() {
+            return Ok(None);
+        }
+
+        GnupgHome::try_new(
+            &self.get_run_dir(&program, GNUPG_DIR, GNUPG_PREFIX),
+            &util::get_gpg_keyring(),
+        )
+        .map(Some)
+    }
+
+    /// Wayland socket shared with programs in a wayland session
+    fn get_wayland_socket(&self) -> Option<PathBuf> {
+        match self.system.wm {
+            Some(WindowManager::Wayland) => self.system.wayland.to_owned(),
+            _ => None,
+        }
+    }
+
+    /// Checks that user defined mounts and devices exist and don't clash with feature mounts
+    pub fn validate(&self, program: &Program) -> AppResult<&Self> {
+        if program.settings.contains(&Feature::Clipboard)
+            && !program.settings.contains(&Feature::Display)
+        {
+            return Err(AppError::Program(
+                "Clipboard is shared through the display, enable Display too".to_string(),
+            ));
+        }
+
+        let mut targets: Vec<PathBuf> = self
+            .get_feature_mounts(&program)?
+            .iter()
+            .map(|mount| mount.target.to_owned())
+            .collect();
+
+        for mount in &program.options.mounts {
+            if !mount.source.exists() {
+                return Err(AppError::File(format!(
+                    "Mount source '{}' doesn't exist",
+                    mount.source.display()
+                )));
+            }
+
+            if !mount.target.is_absolute() {
+                return Err(AppError::Program(format!(
+                    "Mount target '{}' must be an absolute path",
+                    mount.target.display()
+                )));
+            }
+
+            if targets.contains(&mount.target) {
+                return Err(AppError::Program(format!(
+                    "Mount target '{}' is already used",
+                    mount.target.display()
+                )));
+            }
+
+            targets.push(mount.target.to_owned());
+        }
+
+        for device in &program.options.devices {
+            if !device.exists() {
+                return Err(AppError::File(format!(
+                    "Device '{}' doesn't exist",
+                    device.display()
+                )));
+            }
         }
 
-        if program.settings.contains(&Feature::Notification) {
-            args.push_volume("/var/lib/dbus:/var/lib/dbus");
+        for (name, _) in &program.options.env {
+            if name.is_empty() || name.contains('=') {
+                return Err(AppError::Program(format!(
+                    "Invalid environment variable name '{}'",
+                    name
+                )));
+            }
         }
 
+        Ok(self)
+    }
+
+    fn get_run_args(&self, program: &Program, resources: &RunResources) -> AppResult<Vec<String>> {
+        let mut args: Vec<String> = vec!["run", "-ti", "--net=host"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+
         if program.settings.contains(&Feature::Devices) {
-            args.push_volume("/dev:/dev");
+            args.push("--privileged".to_string());
+        }
+
+        if program.settings.contains(&Feature::HomeSandbox) {
+            std::fs::create_dir_all(self.get_sandbox_home(&program))
+                .map_err(|err| AppError::File(err.to_string()))?;
+        }
+
+        self.get_feature_mounts(&program)?
+            .iter()
+            .chain(program.options.mounts.iter())
+            .for_each(|mount| {
+                args.push_volume(mount.to_string());
+            });
+
+        self.get_feature_env(&program).iter().for_each(|env| {
+            args.push_env(env.as_str());
+        });
+
+        if let Some(xauth) = &resources.xauth {
+            let target = Path::new(XAUTH_MOUNT_PATH);
+
+            args.push_volume(Mount::new(xauth.dir(), target, true).to_string());
+            args.push_env(format!("XAUTHORITY={}", target.join(XAUTH_FILE).display()));
         }
 
-        args.push(&cmd_name);
+        if let Some(proxy) = &resources.dbus_proxy {
+            let target = Path::new(DBUS_MOUNT_PATH);
 
-        let mut cmd = Command::new("docker")
-            .args(args)
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .spawn()
-            .map_err(|err| AppError::Docker)?;
+            args.push_volume(Mount::new(proxy.dir(), target, false).to_string());
+            args.push_env(format!(
+                "DBUS_SESSION_BUS_ADDRESS=unix:path={}",
+                target.join(PROXY_SOCKET).display()
+            ));
+        }
+
+        if let (Some(home), Some(socket)) = (&resources.gnupg, &self.system.gpg_agent) {
+            let target = Path::new(GNUPG_MOUNT_PATH);
+
+            args.push_volume(Mount::new(home.dir(), target, false).to_string());
+            args.push_volume(Mount::new(socket, &target.join(AGENT_SOCKET), true).to_string());
+            args.push_env(format!("GNUPGHOME={}", target.display()));
+        }
+
+        program.options.env.iter().for_each(|(name, value)| {
+            args.push_env(format!("{}={}", name, value));
+        });
+
+        program.options.devices.iter().for_each(|device| {
+            args.push_device(device.display().to_string());
+        });
+
+        for class in self.get_device_classes(&program) {
+            let nodes: Vec<&PathBuf> = self
+                .system
+                .devices
+                .iter()
+                .filter(|(found, _)| found == &class)
+                .map(|(_, node)| node)
+                .collect();
+
+            // the rule would allow devices of a class the program didn't get
+            if nodes.is_empty() {
+                warn!("No {} devices found", class);
+                continue;
+            }
+
+            nodes.iter().for_each(|node| {
+                args.push_device(node.display().to_string());
+            });
+            args.push("--device-cgroup-rule".to_string());
+            args.push(class.cgroup_rule().to_string());
+        }
 
-        let status = cmd.wait().map_err(|err| {
-            error!("{}", err.to_string());
-            AppError::Docker
-        })?;
+        Ok(args)
+    }
+
+    fn spawn(&self, args: Vec<String>) -> AppResult<&Self> {
+        let status = self.engine.run(&args)?;
 
         info!("Exited with status {:?}", status);
 
@@ -215,21 +975,32 @@ impl<'a> DockerFacade<'a> {
     }
 }
 
+fn bind(path: &str) -> Mount {
+    Mount::new(Path::new(path), Path::new(path), false)
+}
+
 trait PushArgument<T: Into<String>> {
     fn push_volume(&mut self, v: T) -> &mut Self;
     fn push_env(&mut self, v: T) -> &mut Self;
+    fn push_device(&mut self, v: T) -> &mut Self;
 }
 
-impl<'a> PushArgument<&'a str> for Vec<&'a str> {
-    fn push_volume(&mut self, v: &'a str) -> &mut Self {
-        self.push("-v");
-        self.push(v);
+impl<T: Into<String>> PushArgument<T> for Vec<String> {
+    fn push_volume(&mut self, v: T) -> &mut Self {
+        self.push("-v".to_string());
+        self.push(v.into());
+        self
+    }
+
+    fn push_env(&mut self, v: T) -> &mut Self {
+        self.push("--env".to_string());
+        self.push(v.into());
         self
     }
 
-    fn push_env(&mut self, v: &'a str) -> &mut Self {
-        self.push("--env");
-        self.push(v);
+    fn push_device(&mut self, v: T) -> &mut Self {
+        self.push("--device".to_string());
+        self.push(v.into());
         self
     }
 }
@@ -4,11 +4,15 @@ pub enum AppError {
     DockerStatus(i16),
     File(String),
     Program(String),
+    Step(String, Box<AppError>),
 }
 
 impl std::error::Error for AppError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        None
+        match self {
+            AppError::Step(_, error) => Some(error.as_ref()),
+            _ => None,
+        }
     }
 }
 
@@ -28,6 +32,9 @@ impl std::fmt::Display for AppError {
                 AppError::Program(error) => {
                     Box::leak(format!("Program errors occured: {}", error).into_boxed_str())
                 }
+                AppError::Step(step, error) => {
+                    Box::leak(format!("{} failed: {}", step, error).into_boxed_str())
+                }
             }
         )
     }
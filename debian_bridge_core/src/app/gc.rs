@@ -0,0 +1,141 @@
+use super::{
+    context::{CONTEXT_PREFIX, LOGS_DIR},
+    dbus::{DBUS_DIR, DBUS_PREFIX},
+    error::AppError,
+    gnupg::{GNUPG_DIR, GNUPG_PREFIX},
+    xauth::{XAUTH_DIR, XAUTH_PREFIX},
+};
+use std::{
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+type AppResult<T> = Result<T, AppError>;
+
+/// Resource left behind by removed programs, failed builds or interrupted runs
+#[derive(Debug, Clone, PartialEq)]
+pub enum Garbage {
+    Container { id: String, name: String },
+    Image(String),
+    File(PathBuf),
+}
+
+impl Display for Garbage {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Garbage::Container { id, name } => {
+                write!(f, "container {} ({})", name, &id[..id.len().min(12)])
+            }
+            Garbage::Image(tag) => write!(f, "image {}", tag),
+            Garbage::File(path) => write!(f, "file {}", path.display()),
+        }
+    }
+}
+
+/// Finds build contexts and run directories of finished processes, build logs and run
+/// directories of unknown programs
+pub fn find_cache_garbage(cache_path: &Path, programs: &Vec<String>) -> AppResult<Vec<Garbage>> {
+    let mut garbage = vec![];
+
+    for entry in read_dir(cache_path)? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let prefix = match [CONTEXT_PREFIX, XAUTH_PREFIX, DBUS_PREFIX, GNUPG_PREFIX]
+            .iter()
+            .find(|prefix| name.starts_with(*prefix))
+        {
+            Some(prefix) => prefix,
+            None => continue,
+        };
+
+        let pid = name[prefix.len()..].splitn(2, '-').next();
+        let alive = pid.map_or(false, |pid| {
+            pid == std::process::id().to_string() || Path::new("/proc").join(pid).exists()
+        });
+
+        if !alive {
+            garbage.push(Garbage::File(entry.path()));
+        }
+    }
+
+    for entry in read_dir(&cache_path.join(LOGS_DIR))? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let program = name
+            .trim_end_matches(".log")
+            .rsplitn(2, '-')
+            .nth(1)
+            .map(str::to_string);
+
+        if !program.map_or(false, |program| programs.contains(&program)) {
+            garbage.push(Garbage::File(entry.path()));
+        }
+    }
+
+    for dir in &[XAUTH_DIR, DBUS_DIR, GNUPG_DIR] {
+        for entry in read_dir(&cache_path.join(dir))? {
+            if !programs.contains(&entry.file_name().to_string_lossy().to_string()) {
+                garbage.push(Garbage::File(entry.path()));
+            }
+        }
+    }
+
+    Ok(garbage)
+}
+
+pub fn remove_file(path: &Path) -> AppResult<()> {
+    match path.is_dir() {
+        true => std::fs::remove_dir_all(path),
+        false => std::fs::remove_file(path),
+    }
+    .map_err(|err| AppError::File(err.to_string()))
+}
+
+fn read_dir(path: &Path) -> AppResult<Vec<std::fs::DirEntry>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    std::fs::read_dir(path)
+        .and_then(|entries| entries.collect())
+        .map_err(|err| AppError::File(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_garbage_found_success() {
+        let mut cache_path = std::env::temp_dir();
+        cache_path.push("debian_bridge_gc_test");
+
+        let logs_path = cache_path.join(LOGS_DIR);
+        let own_context = cache_path.join(format!("{}{}-1", CONTEXT_PREFIX, std::process::id()));
+        let stale_context = cache_path.join(format!("{}{}-1", CONTEXT_PREFIX, u32::max_value()));
+        let stale_xauth = cache_path.join(format!("{}{}", XAUTH_PREFIX, u32::max_value()));
+        let xauth_path = cache_path.join(XAUTH_DIR);
+
+        std::fs::create_dir_all(&logs_path).unwrap();
+        std::fs::create_dir_all(&own_context).unwrap();
+        std::fs::create_dir_all(&stale_context).unwrap();
+        std::fs::create_dir_all(&stale_xauth).unwrap();
+        std::fs::create_dir_all(xauth_path.join("foo-bar")).unwrap();
+        std::fs::create_dir_all(xauth_path.join("baz")).unwrap();
+        std::fs::write(logs_path.join("foo-bar-100.log"), "").unwrap();
+        std::fs::write(logs_path.join("baz-100.log"), "").unwrap();
+
+        let mut garbage = find_cache_garbage(&cache_path, &vec!["foo-bar".to_string()]).unwrap();
+        garbage.sort_by_key(|garbage| garbage.to_string());
+
+        assert_eq!(
+            vec![
+                Garbage::File(stale_context.to_owned()),
+                Garbage::File(logs_path.join("baz-100.log")),
+                Garbage::File(stale_xauth.to_owned()),
+                Garbage::File(xauth_path.join("baz")),
+            ],
+            garbage
+        );
+
+        std::fs::remove_dir_all(&cache_path).unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+use super::error::AppError;
+use std::{
+    fs::Permissions,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+type AppResult<T> = Result<T, AppError>;
+
+pub const GNUPG_PREFIX: &str = "gnupg-";
+pub const GNUPG_DIR: &str = "gnupg";
+pub const AGENT_SOCKET: &str = "S.gpg-agent";
+const KEYRING_FILE: &str = "pubring.kbx";
+
+/// GnuPG home of a container, owned by the user and writable, so gpg can keep its trust
+/// database and random seed. It gets a copy of the host public keyring, the agent socket
+/// is mounted into it. The directory is removed when dropped
+pub struct GnupgHome {
+    dir: PathBuf,
+}
+
+impl GnupgHome {
+    pub fn try_new(dir: &Path, keyring: &Option<PathBuf>) -> AppResult<Self> {
+        std::fs::create_dir_all(dir).map_err(|err| AppError::File(err.to_string()))?;
+
+        // gpg warns about a home accessible by other users
+        std::fs::set_permissions(dir, Permissions::from_mode(0o700))
+            .map_err(|err| AppError::File(err.to_string()))?;
+
+        let home = GnupgHome {
+            dir: dir.to_owned(),
+        };
+
+        if let Some(keyring) = keyring {
+            std::fs::copy(keyring, home.dir.join(KEYRING_FILE))
+                .map_err(|err| AppError::File(err.to_string()))?;
+        }
+
+        Ok(home)
+    }
+
+    pub fn dir(&self) -> &Path {
+        self.dir.as_path()
+    }
+}
+
+impl Drop for GnupgHome {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.dir).unwrap_or_else(|err| {
+            warn!("Can't remove '{}': {}", self.dir.display(), err.to_string());
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn home_with_keyring_removed_success() {
+        let path = std::env::temp_dir().join("debian_bridge_gnupg_test");
+        let keyring = path.join("host.kbx");
+        let dir = path.join("home");
+
+        std::fs::create_dir_all(&path).unwrap();
+        std::fs::write(&keyring, "keys").unwrap();
+
+        {
+            let home = GnupgHome::try_new(&dir, &Some(keyring.to_owned())).unwrap();
+            let mode = std::fs::metadata(home.dir()).unwrap().permissions().mode();
+
+            assert_eq!(0o700, mode & 0o777);
+            assert_eq!(
+                "keys",
+                std::fs::read_to_string(dir.join(KEYRING_FILE)).unwrap()
+            );
+        }
+
+        assert!(!dir.exists());
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
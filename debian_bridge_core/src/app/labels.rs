@@ -0,0 +1,111 @@
+use super::{deb::Deb, Program};
+use std::collections::HashMap;
+
+const NAMESPACE: &str = "io.github.22116.debian_bridge";
+const OCI_TITLE: &str = "org.opencontainers.image.title";
+const OCI_VERSION: &str = "org.opencontainers.image.version";
+
+/// Labels of images built by debian_bridge. Containers inherit labels of their images
+pub struct Labels;
+
+impl Labels {
+    pub fn key(name: &str) -> String {
+        format!("{}.{}", NAMESPACE, name)
+    }
+
+    pub fn new(prefix: &str, deb: &Deb, program: &Program, hash: &str) -> Vec<(String, String)> {
+        let mut labels = vec![
+            (Labels::key("prefix"), prefix.to_string()),
+            (Labels::key("program"), program.get_name_short()),
+            (Labels::key("package.name"), deb.package.to_owned()),
+            (Labels::key("package.sha256"), hash.to_string()),
+            (
+                Labels::key("features"),
+                program
+                    .settings
+                    .iter()
+                    .map(|feature| format!("{:?}", feature))
+                    .collect::<Vec<String>>()
+                    .join(","),
+            ),
+            (
+                Labels::key("version"),
+                env!("CARGO_PKG_VERSION").to_string(),
+            ),
+            (OCI_TITLE.to_string(), deb.package.to_owned()),
+        ];
+
+        if let Some(version) = &deb.version {
+            labels.push((Labels::key("package.version"), version.to_owned()));
+            labels.push((OCI_VERSION.to_string(), version.to_owned()));
+        }
+
+        labels
+    }
+
+    /// Whether labels belong to an image built by any debian_bridge version with labelling
+    pub fn is_labelled(labels: &HashMap<String, String>) -> bool {
+        labels.contains_key(&Labels::key("prefix"))
+    }
+
+    /// Returns a program name if labels belong to an image built with a given prefix
+    pub fn get_program(labels: &HashMap<String, String>, prefix: &str) -> Option<String> {
+        if labels.get(&Labels::key("prefix"))? != prefix {
+            return None;
+        }
+
+        labels.get(&Labels::key("program")).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Feature;
+    use std::path::Path;
+
+    #[test]
+    fn labels_match_program_success() {
+        let deb = Deb {
+            package: "foo".to_string(),
+            version: Some("1.0.2".to_string()),
+            license: None,
+            vendor: None,
+            architecture: None,
+            maintainer: None,
+            installed_size: None,
+            dependencies: None,
+            section: None,
+            priority: None,
+            homepage: None,
+            description: None,
+        };
+        let program = Program::new(
+            "foo",
+            Path::new("/tmp/foo.deb"),
+            &vec![Feature::Display, Feature::Sound],
+            &None,
+            &None,
+            &None,
+            &Default::default(),
+        );
+        let labels: HashMap<String, String> = Labels::new("debian_bridge", &deb, &program, "abc")
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            Some(&"Display,Sound".to_string()),
+            labels.get(&Labels::key("features"))
+        );
+        assert_eq!(
+            Some(&"1.0.2".to_string()),
+            labels.get("org.opencontainers.image.version")
+        );
+        assert_eq!(
+            Some("foo".to_string()),
+            Labels::get_program(&labels, "debian_bridge")
+        );
+        assert_eq!(None, Labels::get_program(&labels, "other"));
+        assert_eq!(None, Labels::get_program(&HashMap::new(), "debian_bridge"));
+    }
+}
@@ -1,24 +1,40 @@
+mod bundle;
 mod config;
+mod context;
+mod dbus;
 mod deb;
 mod docker;
 pub mod error;
-mod util;
+mod gc;
+mod gnupg;
+mod labels;
+mod progress;
+mod transaction;
+pub(crate) mod util;
+mod xauth;
 
-use crate::System;
+use crate::{Engine, System};
+use bundle::{Bundle, IMAGE_FILE};
 use colorful::{core::StrMarker, Color, Colorful};
-pub use config::{Config, Feature, Icon, Program};
+pub use config::{Config, Feature, Icon, Mount, Program, RunOptions};
+use context::BuildContext;
 use deb::Deb;
+pub use deb::PackageChange;
 use docker::DockerFacade;
 use error::AppError;
+pub use gc::Garbage;
+use labels::Labels;
+pub use progress::{BuildProgress, ProgressHandler};
 use serde_json::to_string;
-use shiplift::Docker;
 use std::{
+    cmp::Ordering,
     collections::HashMap,
     error::Error,
     fmt::{Display, Formatter},
     net::IpAddr,
     path::{Path, PathBuf},
 };
+use transaction::Transaction;
 
 type AppResult<T> = Result<T, AppError>;
 
@@ -36,6 +52,27 @@ impl FeaturesList {
         list.insert(Feature::Notification, true);
         list.insert(Feature::Time, true);
         list.insert(Feature::HomePersistent, true);
+        list.insert(Feature::HomeSandbox, dirs::data_dir().is_some());
+        list.insert(Feature::Persistent, true);
+        list.insert(Feature::Printing, system.cups.is_some());
+        list.insert(Feature::Portal, system.portal);
+        list.insert(Feature::Clipboard, system.wm.is_some());
+        list.insert(Feature::SshAgent, system.ssh_agent.is_some());
+        list.insert(Feature::GpgAgent, system.gpg_agent.is_some());
+
+        for feature in vec![
+            Feature::Camera,
+            Feature::AudioDevices,
+            Feature::Usb,
+            Feature::Input,
+            Feature::Gpu,
+        ] {
+            let available = feature.device_class().map_or(false, |class| {
+                system.devices.iter().any(|(found, _)| found == &class)
+            });
+
+            list.insert(feature, available);
+        }
 
         Self { list }
     }
@@ -76,15 +113,21 @@ impl Display for FeaturesList {
 
 /// Main structure to run application
 ///
+/// Methods block the calling thread until the engine responds. Called from a task of an
+/// async application, they hold one of its worker threads, so call them with
+/// `tokio_threadpool::blocking` or from a separate thread. Never call them from a task of
+/// a current thread runtime the `Executor` was made from, it's a deadlock
+///
 /// # Example
 /// ```no_run
-/// use debian_bridge_core::{App, Config, Docker, System};
+/// use debian_bridge_core::{engine, App, Config, Executor, System};
 /// use std::path::Path;
 ///
-/// let docker = Docker::new();
+/// let executor = Executor::new().unwrap();
 /// let config = Config::deserialize(Path::new("./cfg")).unwrap();
-/// let system = System::try_new(&docker).unwrap();
-/// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, &docker);
+/// let engine = engine::connect(&config.engine, &config.docker, &executor).unwrap();
+/// let system = System::try_new(engine.as_ref()).unwrap();
+/// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, engine.as_ref());
 /// //...
 /// app.save(Path::new("./cfg")).unwrap();
 /// ```
@@ -111,13 +154,14 @@ impl<'a> App<'a> {
     ///
     /// # Example
     /// ```no_run
-    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use debian_bridge_core::{engine, App, Config, Executor, System};
     /// # use std::path::Path;
     /// #
-    /// # let docker = Docker::new();
+    /// # let executor = Executor::new().unwrap();
     /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
-    /// # let system = System::try_new(&docker).unwrap();
-    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, &docker);
+    /// # let engine = engine::connect(&config.engine, &config.docker, &executor).unwrap();
+    /// # let system = System::try_new(engine.as_ref()).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, engine.as_ref());
     /// app.remove("foo-program").unwrap();
     /// app.save(Path::new("./cfg")).unwrap();
     /// ```
@@ -127,26 +171,54 @@ impl<'a> App<'a> {
             .find(program.into())
             .ok_or(AppError::Program("Input program doesn't exist".to_str()))?
             .0;
-
-        match self.docker.delete(&program) {
-            Ok(_) => (),
-            Err(AppError::DockerStatus(404)) => (),
-            Err(err) => return Err(err),
+        let entry_path = match program.icon {
+            Some(_) => Some(get_entry_path(&program.get_name_short())?),
+            None => None,
         };
-        self.config.remove(&program)?;
+        let mut transaction = Transaction::new();
+        let restored = program.to_owned();
 
-        if let Some(_) = program.icon {
-            let mut path = dirs::desktop_dir().unwrap();
-            let name = format!("{}.desktop", program.get_name_short());
+        transaction.step(
+            self,
+            "Updating a configuration",
+            |app| app.config.remove(&program).map(|_| ()),
+            move |app| app.config.push(&restored).map(|_| ()),
+        )?;
 
-            path.push(name);
+        if let Some(path) = entry_path {
+            let entry = std::fs::read(&path).ok();
+            let restored_path = path.to_owned();
 
-            std::fs::remove_file(path).unwrap_or_else(|err| {
-                error!("Can't remove an entry file: '{}'", err.to_string());
-                ()
-            });
+            transaction.step(
+                self,
+                "Removing a desktop entry",
+                |_| {
+                    std::fs::remove_file(&path).unwrap_or_else(|err| {
+                        warn!("Can't remove an entry file: '{}'", err.to_string());
+                    });
+                    Ok(())
+                },
+                move |_| match entry {
+                    Some(entry) => std::fs::write(&restored_path, entry)
+                        .map_err(|err| AppError::File(err.to_string())),
+                    None => Ok(()),
+                },
+            )?;
         }
 
+        transaction.step(
+            self,
+            "Removing an image",
+            |app| match app.docker.delete(&program) {
+                Ok(_) => Ok(()),
+                Err(AppError::DockerStatus(404)) => Ok(()),
+                Err(err) => Err(err),
+            },
+            |_| Ok(()),
+        )?;
+
+        transaction.commit();
+
         Ok(self)
     }
 
@@ -154,14 +226,15 @@ impl<'a> App<'a> {
     ///
     /// # Example
     /// ```no_run
-    /// # use debian_bridge_core::{App, Config, Docker, System, Feature};
+    /// # use debian_bridge_core::{engine, App, Config, Executor, System, Feature};
     /// # use std::path::Path;
     /// #
-    /// # let docker = Docker::new();
+    /// # let executor = Executor::new().unwrap();
     /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
-    /// # let system = System::try_new(&docker).unwrap();
-    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, &docker);
-    /// app.create(Path::new("./package.deb"), &vec![Feature::Display], &None, &None, &None).unwrap();
+    /// # let engine = engine::connect(&config.engine, &config.docker, &executor).unwrap();
+    /// # let system = System::try_new(engine.as_ref()).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, engine.as_ref());
+    /// app.create(Path::new("./package.deb"), &vec![Feature::Display], &None, &None, &None, &Default::default()).unwrap();
     /// app.save(Path::new("./cfg")).unwrap();
     /// ```
     pub fn create(
@@ -171,6 +244,7 @@ impl<'a> App<'a> {
         icon: &Option<Icon>,
         cmd: &Option<String>,
         deps: &Option<String>,
+        options: &RunOptions,
     ) -> AppResult<&Self> {
         if !self.features.validate(&settings) {
             return Err(AppError::Program(
@@ -178,75 +252,511 @@ impl<'a> App<'a> {
             ));
         }
 
+        if settings.contains(&Feature::HomePersistent) && settings.contains(&Feature::HomeSandbox) {
+            return Err(AppError::Program(
+                "Home persistent and home sandboxed features can not be used together".to_string(),
+            ));
+        }
+
         let deb = Deb::try_new(app_path)?;
-        let program = Program::new(&deb.package, &app_path, &settings, &icon, &cmd, &deps);
-        let mut app_tmp_path = self.cache_path.to_owned();
+        let mut program = Program::new(
+            &deb.package,
+            &app_path,
+            &settings,
+            &icon,
+            &cmd,
+            &deps,
+            &options,
+        );
 
-        std::fs::create_dir_all(&app_tmp_path).map_err(|err| AppError::File(err.to_string()))?;
-        app_tmp_path.push(Path::new("tmp.deb"));
-        std::fs::copy(app_path, &app_tmp_path).map_err(|err| AppError::File(err.to_string()))?;
+        program.version = deb.version.to_owned();
+        self.docker.validate(&program)?;
 
-        let mut dockerfile = util::gen_dockerfile(&deb, &program)?;
+        let context = self.prepare_context(&deb, &program)?;
+        let mut transaction = Transaction::new();
+        let pushed = program.to_owned();
+        let built = program.to_owned();
 
-        debug!("Generated dockerfile:\n{}", dockerfile);
+        transaction.step(
+            self,
+            "Updating a configuration",
+            |app| app.config.push(&program).map(|_| ()),
+            move |app| app.config.remove(&pushed).map(|_| ()),
+        )?;
+        transaction.step(
+            self,
+            "Building an image",
+            |app| {
+                let keep = app.config.keep_builds;
 
-        let mut dockerfile_path = self.cache_path.to_owned();
-        dockerfile_path.push(Path::new("Dockerfile"));
+                app.docker
+                    .create(&deb.package, &deb.version, context.path(), keep, false)
+                    .map(|_| ())
+            },
+            move |app| app.docker.delete(&built).map(|_| ()),
+        )?;
 
-        std::fs::write(&dockerfile_path, dockerfile)
-            .map_err(|err| AppError::File(err.to_string()))?;
+        if let Some(icon) = &icon {
+            let package = deb.package.to_owned();
 
-        self.config.push(&program)?;
-        self.docker.create(&deb.package)?;
+            transaction.step(
+                self,
+                "Creating a desktop entry",
+                |app| app.create_entry(&icon, &deb).map(|_| ()),
+                move |_| {
+                    std::fs::remove_file(get_entry_path(&package)?)
+                        .map_err(|err| AppError::File(err.to_string()))
+                },
+            )?;
+        }
 
-        std::fs::remove_file(&dockerfile_path).map_err(|err| AppError::File(err.to_string()))?;
-        std::fs::remove_file(&app_tmp_path).map_err(|err| AppError::File(err.to_string()))?;
+        transaction.commit();
 
-        if let Some(icon) = &icon {
-            self.create_entry(&icon, &deb).unwrap_or_else(|err| {
-                warn!("{}", err.to_string());
-                &self
-            });
+        Ok(self)
+    }
+
+    /// Rebuilds an existing program from a newer package with the stored settings
+    ///
+    /// The active image is switched only after the new build succeeds
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{engine, App, Config, Executor, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let executor = Executor::new().unwrap();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let engine = engine::connect(&config.engine, &config.docker, &executor).unwrap();
+    /// # let system = System::try_new(engine.as_ref()).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, engine.as_ref());
+    /// app.update("foo_program", Path::new("./package_new.deb")).unwrap();
+    /// app.save(Path::new("./cfg")).unwrap();
+    /// ```
+    pub fn update<T: Into<String>>(&mut self, program: T, app_path: &Path) -> AppResult<&Self> {
+        let current = self
+            .config
+            .find(program)
+            .ok_or(AppError::Program("Program not found".to_string()))?
+            .0;
+        let deb = Deb::try_new(app_path)?;
+
+        if deb.package != current.get_name_short() {
+            return Err(AppError::Program(format!(
+                "Package '{}' doesn't match program '{}'",
+                deb.package,
+                current.get_name_short()
+            )));
+        }
+
+        match (&current.version, &deb.version) {
+            (Some(installed), Some(version))
+                if deb::compare_versions(version, installed) != Ordering::Greater =>
+            {
+                return Err(AppError::Program(format!(
+                    "Package version '{}' is not newer than installed '{}'",
+                    version, installed
+                )));
+            }
+            (Some(_), Some(_)) => (),
+            _ => warn!("Can't compare package versions, updating anyway"),
+        }
+
+        let mut program = current.to_owned();
+
+        program.path = app_path.to_owned();
+        program.version = deb.version.to_owned();
+        self.docker.validate(&program)?;
+
+        let context = self.prepare_context(&deb, &program)?;
+        let mut transaction = Transaction::new();
+
+        transaction.step(
+            self,
+            "Updating a configuration",
+            |app| app.config.update(&program).map(|_| ()),
+            |app| app.config.update(&current).map(|_| ()),
+        )?;
+        transaction.step(
+            self,
+            "Building an image",
+            |app| {
+                let keep = app.config.keep_builds;
+
+                app.docker
+                    .create(&deb.package, &deb.version, context.path(), keep, false)
+                    .map(|_| ())
+            },
+            |_| Ok(()),
+        )?;
+
+        transaction.commit();
+
+        if program.settings.contains(&Feature::Persistent) {
+            info!(
+                "Persistent container keeps the previous version, run `reset {}` to switch",
+                program.get_name_short()
+            );
+        }
+
+        Ok(self)
+    }
+
+    /// Rebuilds a program from its stored package with a fresh base image and without
+    /// build cache. Returns packages changed in the image
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{engine, App, Config, Executor, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let executor = Executor::new().unwrap();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let engine = engine::connect(&config.engine, &config.docker, &executor).unwrap();
+    /// # let system = System::try_new(engine.as_ref()).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, engine.as_ref());
+    /// for change in app.rebuild("foo_program").unwrap() {
+    ///     println!("{}", change);
+    /// }
+    /// ```
+    pub fn rebuild<T: Into<String>>(&mut self, program: T) -> AppResult<Vec<PackageChange>> {
+        let program = self
+            .config
+            .find(program)
+            .ok_or(AppError::Program("Program not found".to_string()))?
+            .0;
+        let deb = Deb::try_new(&program.path)?;
+        let before = self.docker.get_packages(&program).unwrap_or_else(|err| {
+            warn!("Can't list packages of the current build: {}", err);
+            HashMap::new()
+        });
+        let context = self.prepare_context(&deb, &program)?;
+        let keep = self.config.keep_builds;
+
+        self.docker
+            .create(&deb.package, &deb.version, context.path(), keep, true)?;
+
+        let after = self.docker.get_packages(&program)?;
+
+        Ok(PackageChange::diff(&before, &after))
+    }
+
+    /// Removes containers, images and cache files which don't belong to configured programs.
+    /// Returns found resources, nothing is removed in a dry run
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{engine, App, Config, Executor, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let executor = Executor::new().unwrap();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let engine = engine::connect(&config.engine, &config.docker, &executor).unwrap();
+    /// # let system = System::try_new(engine.as_ref()).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, engine.as_ref());
+    /// for garbage in app.gc(true).unwrap() {
+    ///     println!("{}", garbage);
+    /// }
+    /// ```
+    pub fn gc(&self, dry_run: bool) -> AppResult<Vec<Garbage>> {
+        let mut garbage = self.docker.find_garbage(&self.config.programs)?;
+
+        garbage.append(&mut gc::find_cache_garbage(&self.cache_path, &self.list())?);
+
+        if dry_run {
+            return Ok(garbage);
         }
 
+        Ok(garbage
+            .into_iter()
+            .filter(|garbage| match self.docker.remove_garbage(garbage) {
+                Ok(_) => true,
+                Err(err) => {
+                    warn!("Can't remove {}: {}", garbage, err);
+                    false
+                }
+            })
+            .collect())
+    }
+
+    /// Saves a program image together with its definition to a tarball
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{engine, App, Config, Executor, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let executor = Executor::new().unwrap();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let engine = engine::connect(&config.engine, &config.docker, &executor).unwrap();
+    /// # let system = System::try_new(engine.as_ref()).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, engine.as_ref());
+    /// app.export_image("foo_program", Path::new("./foo_program.tar")).unwrap();
+    /// ```
+    pub fn export_image<T: Into<String>>(&self, program: T, file: &Path) -> AppResult<&Self> {
+        let program = self
+            .config
+            .find(program)
+            .ok_or(AppError::Program("Program not found".to_string()))?
+            .0;
+        let context = BuildContext::try_new(&self.cache_path)?;
+
+        self.docker
+            .export(&program, &context.path().join(IMAGE_FILE))?;
+        Bundle::new(&self.prefix, &program).pack(context.path(), file)?;
+
         Ok(self)
     }
 
+    /// Loads a program saved with `export_image`. Returns the program name
+    ///
+    /// Mounts under the home of the exporting user are moved to the home of the current user,
+    /// then the program is validated like a created one
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{engine, App, Config, Executor, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let executor = Executor::new().unwrap();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let engine = engine::connect(&config.engine, &config.docker, &executor).unwrap();
+    /// # let system = System::try_new(engine.as_ref()).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, engine.as_ref());
+    /// app.import_image(Path::new("./foo_program.tar")).unwrap();
+    /// app.save(Path::new("./cfg")).unwrap();
+    /// ```
+    pub fn import_image(&mut self, file: &Path) -> AppResult<String> {
+        let context = BuildContext::try_new(&self.cache_path)?;
+        let bundle = Bundle::unpack(file, context.path())?;
+        let home =
+            dirs::home_dir().ok_or(AppError::File("Can not find a home directory".into()))?;
+        let program = bundle.rebase(&home, &util::get_container_home()?);
+
+        if bundle.prefix != self.prefix {
+            return Err(AppError::Program(format!(
+                "Image was exported with a different prefix '{}'",
+                bundle.prefix
+            )));
+        }
+
+        if self.config.find(program.get_name_short()).is_some() {
+            return Err(AppError::Program(format!(
+                "Program '{}' already exists, remove it first",
+                program.get_name_short()
+            )));
+        }
+
+        if !self.features.validate(&program.settings) {
+            return Err(AppError::Program(
+                "Imported program uses an unavailable feature".to_string(),
+            ));
+        }
+
+        self.docker.validate(&program)?;
+
+        let mut transaction = Transaction::new();
+        let imported = program.to_owned();
+
+        transaction.step(
+            self,
+            "Importing an image",
+            |app| {
+                app.docker
+                    .import(&program, &context.path().join(IMAGE_FILE))
+                    .map(|_| ())
+            },
+            move |app| app.docker.delete(&imported).map(|_| ()),
+        )?;
+        transaction.step(
+            self,
+            "Updating a configuration",
+            |app| app.config.push(&program).map(|_| ()),
+            |_| Ok(()),
+        )?;
+
+        transaction.commit();
+
+        if !program.path.exists() {
+            warn!(
+                "Package '{}' is not available, `update` it from a package to rebuild",
+                program.path.display()
+            );
+        }
+
+        Ok(program.get_name_short())
+    }
+
     /// Runs existed program
     ///
+    /// Existing host files among arguments are mounted into the container and passed to
+    /// the program with container paths
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{engine, App, Config, Executor, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let executor = Executor::new().unwrap();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let engine = engine::connect(&config.engine, &config.docker, &executor).unwrap();
+    /// # let system = System::try_new(engine.as_ref()).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, engine.as_ref());
+    /// app.run("foo_program", &vec!["./document.odt".to_string()]).unwrap();
+    /// ```
+    pub fn run<T: Into<String>>(&self, program: T, arguments: &Vec<String>) -> AppResult<&Self> {
+        let program = self
+            .config
+            .find(program)
+            .ok_or(AppError::Program("Program not found".to_string()))?
+            .0;
+
+        self.docker.run(&program, &arguments)?;
+        Ok(self)
+    }
+
+    /// Switches the active image of a program to a previous build, or to the newest build
+    /// of a given package version
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{engine, App, Config, Executor, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let executor = Executor::new().unwrap();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let engine = engine::connect(&config.engine, &config.docker, &executor).unwrap();
+    /// # let system = System::try_new(engine.as_ref()).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, engine.as_ref());
+    /// app.rollback("foo_program", &Some("1.0.2".to_string())).unwrap();
+    /// ```
+    pub fn rollback<T: Into<String>>(
+        &mut self,
+        program: T,
+        version: &Option<String>,
+    ) -> AppResult<&Self> {
+        let program = self
+            .config
+            .find(program)
+            .ok_or(AppError::Program("Program not found".to_string()))?
+            .0;
+
+        self.docker.rollback(&program, &version)?;
+        Ok(self)
+    }
+
+    /// Removes a persistent container of a program, so the next run starts from a clean state
+    ///
     /// # Example
     /// ```no_run
-    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use debian_bridge_core::{engine, App, Config, Executor, System};
     /// # use std::path::Path;
     /// #
-    /// # let docker = Docker::new();
+    /// # let executor = Executor::new().unwrap();
     /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
-    /// # let system = System::try_new(&docker).unwrap();
-    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, &docker);
-    /// app.run("foo_program").unwrap();
+    /// # let engine = engine::connect(&config.engine, &config.docker, &executor).unwrap();
+    /// # let system = System::try_new(engine.as_ref()).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, engine.as_ref());
+    /// app.reset("foo_program").unwrap();
     /// ```
-    pub fn run<T: Into<String>>(&self, program: T) -> AppResult<&Self> {
+    pub fn reset<T: Into<String>>(&mut self, program: T) -> AppResult<&Self> {
         let program = self
             .config
             .find(program)
             .ok_or(AppError::Program("Program not found".to_string()))?
             .0;
 
-        self.docker.run(&program)?;
+        if !program.settings.contains(&Feature::Persistent) {
+            return Err(AppError::Program(format!(
+                "Program '{}' is not persistent",
+                program.get_name_short()
+            )));
+        }
+
+        self.docker.reset(&program)?;
         Ok(self)
     }
 
+    /// Starts an interactive shell in a program's image with the same mounts as `run`
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{engine, App, Config, Executor, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let executor = Executor::new().unwrap();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let engine = engine::connect(&config.engine, &config.docker, &executor).unwrap();
+    /// # let system = System::try_new(engine.as_ref()).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, engine.as_ref());
+    /// app.shell("foo_program").unwrap();
+    /// ```
+    pub fn shell<T: Into<String>>(&self, program: T) -> AppResult<&Self> {
+        let program = self
+            .config
+            .find(program)
+            .ok_or(AppError::Program("Program not found".to_string()))?
+            .0;
+
+        self.docker.shell(&program)?;
+        Ok(self)
+    }
+
+    /// Executes a command in a running instance of a program
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{engine, App, Config, Executor, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let executor = Executor::new().unwrap();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let engine = engine::connect(&config.engine, &config.docker, &executor).unwrap();
+    /// # let system = System::try_new(engine.as_ref()).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, engine.as_ref());
+    /// app.exec("foo_program", &vec!["ls".to_string(), "-la".to_string()]).unwrap();
+    /// ```
+    pub fn exec<T: Into<String>>(&self, program: T, command: &Vec<String>) -> AppResult<&Self> {
+        let program = self
+            .config
+            .find(program)
+            .ok_or(AppError::Program("Program not found".to_string()))?
+            .0;
+
+        self.docker.exec(&program, &command)?;
+        Ok(self)
+    }
+
+    /// Sets a handler receiving image build progress, build output is logged otherwise
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{engine, App, Config, Executor, System};
+    /// # use std::{path::Path, sync::Arc};
+    /// #
+    /// # let executor = Executor::new().unwrap();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let engine = engine::connect(&config.engine, &config.docker, &executor).unwrap();
+    /// # let system = System::try_new(engine.as_ref()).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, engine.as_ref());
+    /// app.set_progress_handler(Arc::new(|progress| println!("{}", progress)));
+    /// ```
+    pub fn set_progress_handler(&mut self, handler: ProgressHandler) -> &Self {
+        self.docker.set_progress_handler(handler);
+        self
+    }
+
     /// Saves current application configuration
     ///
     /// # Example
     /// ```no_run
-    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use debian_bridge_core::{engine, App, Config, Executor, System};
     /// # use std::path::Path;
     /// #
-    /// # let docker = Docker::new();
+    /// # let executor = Executor::new().unwrap();
     /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
-    /// # let system = System::try_new(&docker).unwrap();
-    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, &docker);
+    /// # let engine = engine::connect(&config.engine, &config.docker, &executor).unwrap();
+    /// # let system = System::try_new(engine.as_ref()).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, engine.as_ref());
     /// app.save(Path::new("./cfg_new")).unwrap();
     /// ```
     pub fn save(&self, path: &Path) -> AppResult<&Self> {
@@ -259,13 +769,14 @@ impl<'a> App<'a> {
     ///
     /// # Example
     /// ```no_run
-    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use debian_bridge_core::{engine, App, Config, Executor, System};
     /// # use std::path::Path;
     /// #
-    /// # let docker = Docker::new();
+    /// # let executor = Executor::new().unwrap();
     /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
-    /// # let system = System::try_new(&docker).unwrap();
-    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, &docker);
+    /// # let engine = engine::connect(&config.engine, &config.docker, &executor).unwrap();
+    /// # let system = System::try_new(engine.as_ref()).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, engine.as_ref());
     /// ```
     pub fn new<T: Into<String>, S: Into<String>>(
         package_name: T,
@@ -273,21 +784,43 @@ impl<'a> App<'a> {
         cache_path: &Path,
         config: &Config,
         system: &'a System,
-        docker: &'a Docker,
+        engine: &'a dyn Engine,
     ) -> Self {
         let package_name = package_name.into();
         let prefix = prefix.into();
+        let mut data_path = dirs::data_dir().unwrap_or(cache_path.to_owned());
+
+        data_path.push(&package_name);
 
         App {
             package_name,
             prefix: prefix.to_owned(),
             config: config.to_owned(),
-            docker: DockerFacade::new(docker, system, prefix, cache_path),
+            docker: DockerFacade::new(engine, system, prefix, cache_path, &data_path),
             cache_path: cache_path.to_owned(),
             features: FeaturesList::new(&system),
         }
     }
 
+    fn prepare_context(&self, deb: &Deb, program: &Program) -> AppResult<BuildContext> {
+        let context = BuildContext::try_new(&self.cache_path)?;
+        let hash = util::get_file_hash(&program.path)?;
+        let labels = Labels::new(&self.prefix, &deb, &program, &hash);
+        let dockerfile = util::gen_dockerfile(&deb, &program, &labels)?;
+
+        debug!("Generated dockerfile:\n{}", dockerfile);
+
+        context
+            .copy(&program.path, "tmp.deb")?
+            .write("Dockerfile", dockerfile)?;
+
+        if program.settings.contains(&Feature::Portal) {
+            context.write(util::XDG_OPEN_FILE, util::XDG_OPEN_SCRIPT)?;
+        }
+
+        Ok(context)
+    }
+
     fn create_entry(&self, icon: &Icon, deb: &Deb) -> AppResult<&Self> {
         let entry = util::gen_desktop_entry(
             &self.package_name,
@@ -299,7 +832,7 @@ impl<'a> App<'a> {
         );
 
         let entry = entry.map_err(|err| AppError::File(err.to_string()))?;
-        let mut path = dirs::desktop_dir().unwrap();
+        let path = get_entry_path(&deb.package)?;
 
         debug!(
             "Generated new entry in '{}':\n{}",
@@ -307,14 +840,587 @@ impl<'a> App<'a> {
             entry
         );
 
-        if !path.exists() {
-            std::fs::create_dir(&path).map_err(|err| AppError::File(err.to_string()))?;
+        std::fs::write(path, entry).map_err(|err| AppError::File(err.to_string()))?;
+
+        Ok(self)
+    }
+}
+
+fn get_entry_path(name: &String) -> AppResult<PathBuf> {
+    let mut path = dirs::desktop_dir().unwrap();
+
+    if !path.exists() {
+        std::fs::create_dir(&path).map_err(|err| AppError::File(err.to_string()))?;
+    }
+
+    path.push(format!("{}.desktop", name));
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        engine::{fake::FakeEngine, ContainerInfo},
+        sys::driver::{DeviceClass, EngineVersion, SoundDriver, WindowManager},
+    };
+    use std::process::Command;
+
+    /// Cache directory, fake engine and host system of a test. The cache directory is
+    /// removed when dropped
+    struct Fixture {
+        cache_path: PathBuf,
+        engine: FakeEngine,
+        system: System,
+        config: Config,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let mut cache_path = std::env::temp_dir();
+
+            cache_path.push(format!("debian_bridge_app_test_{}", name));
+            std::fs::create_dir_all(&cache_path).unwrap();
+
+            Fixture {
+                cache_path,
+                engine: FakeEngine::new(),
+                system: System {
+                    wm: None,
+                    sd: None,
+                    engine: EngineVersion {
+                        name: "Fake".to_string(),
+                        version: "1.0".to_string(),
+                        endpoint: None,
+                    },
+                    wayland: None,
+                    pulse: None,
+                    pulse_cookie: None,
+                    pipewire: None,
+                    cups: None,
+                    portal: false,
+                    devices: vec![],
+                    ssh_agent: None,
+                    gpg_agent: None,
+                },
+                config: Config::default(),
+            }
+        }
+
+        fn app(&self) -> App<'_> {
+            App::new(
+                "debian_bridge",
+                "debian_bridge",
+                &self.cache_path,
+                &self.config,
+                &self.system,
+                &self.engine,
+            )
         }
 
-        path.push(format!("{}.desktop", deb.package));
+        /// Creates an empty file in the cache directory, e.g. a socket
+        fn touch(&self, name: &str) -> PathBuf {
+            let path = self.cache_path.join(name);
 
-        std::fs::write(path, entry).map_err(|err| AppError::File(err.to_string()))?;
+            std::fs::write(&path, "").unwrap();
+            path
+        }
 
-        Ok(self)
+        /// Builds a `foo` package of a given version
+        fn deb(&self, version: &str) -> PathBuf {
+            let dir = &self.cache_path;
+            let deb = dir.join(format!("foo_{}.deb", version));
+
+            std::fs::write(dir.join("debian-binary"), "2.0\n").unwrap();
+            std::fs::write(
+                dir.join("control"),
+                format!("Package: foo\nVersion: {}\nDescription: Foo\n", version),
+            )
+            .unwrap();
+
+            Command::new("tar")
+                .args(&["czf", "control.tar.gz", "./control"])
+                .current_dir(dir)
+                .status()
+                .unwrap();
+            Command::new("ar")
+                .args(&[
+                    "rc",
+                    deb.to_str().unwrap(),
+                    "debian-binary",
+                    "control.tar.gz",
+                ])
+                .current_dir(dir)
+                .status()
+                .unwrap();
+
+            deb
+        }
+
+        fn create(&self, app: &mut App, settings: Vec<Feature>) -> AppResult<()> {
+            app.create(
+                &self.deb("1.0.0"),
+                &settings,
+                &None,
+                &None,
+                &None,
+                &RunOptions::default(),
+            )
+            .map(|_| ())
+        }
+
+        /// Creates `foo` with given features and returns arguments of its run
+        fn run(&self, settings: Vec<Feature>) -> Vec<String> {
+            self.run_with(settings, RunOptions::default())
+        }
+
+        fn run_with(&self, settings: Vec<Feature>, options: RunOptions) -> Vec<String> {
+            let mut app = self.app();
+
+            app.create(&self.deb("1.0.0"), &settings, &None, &None, &None, &options)
+                .unwrap();
+            app.run("foo", &vec![]).unwrap();
+
+            self.engine.commands().pop().unwrap()
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.cache_path).unwrap_or_default();
+        }
+    }
+
+    /// Values of an option repeated in run arguments
+    fn get_values<'a>(command: &'a Vec<String>, option: &str) -> Vec<&'a str> {
+        command
+            .iter()
+            .zip(command.iter().skip(1))
+            .filter(|(arg, _)| *arg == option)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    fn assert_step(result: AppResult<&App>, step: &str) {
+        match result.err() {
+            Some(AppError::Step(name, _)) => assert_eq!(step, name),
+            _ => panic!("'{}' step must fail", step),
+        }
+    }
+
+    #[test]
+    fn program_lifecycle_success() {
+        let fixture = Fixture::new("lifecycle");
+        let engine = &fixture.engine;
+        let mut app = fixture.app();
+
+        fixture.create(&mut app, vec![Feature::Time]).unwrap();
+
+        assert_eq!(vec!["foo".to_string()], app.list());
+        assert_eq!(2, engine.tags().len());
+        assert!(engine
+            .tags()
+            .contains(&"debian_bridge_foo:latest".to_string()));
+
+        let log = std::fs::read_dir(fixture.cache_path.join(context::LOGS_DIR))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+
+        assert!(std::fs::read_to_string(log)
+            .unwrap()
+            .contains(r#"{"stream":"Step 1/1 : FROM debian:9-slim\n"}"#));
+
+        app.run("foo", &vec![]).unwrap();
+
+        let command = engine.commands().pop().unwrap();
+
+        assert!(command.contains(&"--rm".to_string()));
+        assert!(command.contains(&"/etc/localtime:/etc/localtime".to_string()));
+        assert_eq!(Some(&"debian_bridge_foo".to_string()), command.last());
+
+        app.remove("foo").unwrap();
+
+        assert!(app.list().is_empty());
+        assert!(engine.tags().is_empty());
+    }
+
+    #[test]
+    fn create_rolls_back_failed_build() {
+        let fixture = Fixture::new("failed_build");
+        let mut app = fixture.app();
+
+        fixture.engine.fail("build");
+
+        assert_step(
+            app.create(
+                &fixture.deb("1.0.0"),
+                &vec![],
+                &None,
+                &None,
+                &None,
+                &RunOptions::default(),
+            ),
+            "Building an image",
+        );
+        assert!(app.list().is_empty());
+        assert!(fixture.engine.tags().is_empty());
+        assert!(std::fs::read_dir(&fixture.cache_path)
+            .unwrap()
+            .all(|entry| !entry
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .starts_with(context::CONTEXT_PREFIX)));
+    }
+
+    #[test]
+    fn create_rolls_back_built_image() {
+        let fixture = Fixture::new("failed_tag");
+        let mut app = fixture.app();
+
+        fixture.engine.fail("tag");
+
+        assert_step(
+            app.create(
+                &fixture.deb("1.0.0"),
+                &vec![],
+                &None,
+                &None,
+                &None,
+                &RunOptions::default(),
+            ),
+            "Building an image",
+        );
+        assert!(app.list().is_empty());
+        assert!(fixture.engine.tags().is_empty());
+    }
+
+    #[test]
+    fn remove_restores_failed_delete() {
+        let fixture = Fixture::new("failed_remove");
+        let mut app = fixture.app();
+
+        fixture.create(&mut app, vec![]).unwrap();
+        fixture.engine.fail("delete_image");
+
+        assert_step(app.remove("foo"), "Removing an image");
+        assert_eq!(vec!["foo".to_string()], app.list());
+    }
+
+    #[test]
+    fn remove_finds_unlabelled_containers_success() {
+        let fixture = Fixture::new("unlabelled");
+        let engine = &fixture.engine;
+        let mut app = fixture.app();
+
+        fixture.create(&mut app, vec![]).unwrap();
+        engine.add_container(ContainerInfo {
+            id: "abc".to_string(),
+            names: vec!["/serene_turing".to_string()],
+            image: "debian_bridge_foo".to_string(),
+            labels: HashMap::new(),
+            state: "running".to_string(),
+        });
+        app.exec("foo", &vec!["ls".to_string()]).unwrap();
+
+        assert_eq!(
+            Some(vec![
+                "exec".to_string(),
+                "-ti".to_string(),
+                "abc".to_string(),
+                "ls".to_string()
+            ]),
+            engine.commands().pop()
+        );
+
+        app.remove("foo").unwrap();
+
+        assert!(engine.containers(true).unwrap().is_empty());
+        assert!(engine.tags().is_empty());
+    }
+
+    #[test]
+    fn import_validates_program_success() {
+        let mut fixture = Fixture::new("import");
+        let file = fixture.cache_path.join("foo.tar");
+
+        fixture.system.sd = Some(SoundDriver::Alsa);
+
+        {
+            let mut app = fixture.app();
+
+            fixture.create(&mut app, vec![Feature::Sound]).unwrap();
+            app.export_image("foo", &file).unwrap();
+            app.remove("foo").unwrap();
+        }
+
+        fixture.system.sd = None;
+
+        let mut app = fixture.app();
+
+        assert!(app
+            .import_image(&file)
+            .err()
+            .map_or(false, |err| err.to_string().contains("unavailable feature")));
+        assert!(app.list().is_empty());
+        assert!(fixture.engine.tags().is_empty());
+    }
+
+    #[test]
+    fn update_and_rollback_success() {
+        let fixture = Fixture::new("update");
+        let engine = &fixture.engine;
+        let mut app = fixture.app();
+        let get_active_version = || {
+            engine
+                .images()
+                .unwrap()
+                .into_iter()
+                .find(|image| image.tags.contains(&"debian_bridge_foo:latest".to_string()))
+                .and_then(|image| image.labels.get(&Labels::key("package.version")).cloned())
+        };
+
+        fixture.create(&mut app, vec![Feature::Persistent]).unwrap();
+
+        assert!(app.update("foo", &fixture.deb("1.0.0")).is_err());
+
+        app.update("foo", &fixture.deb("1.1.0")).unwrap();
+
+        assert_eq!(Some("1.1.0".to_string()), get_active_version());
+        assert_eq!(3, engine.tags().len());
+
+        app.rollback("foo", &Some("1.0.0".to_string())).unwrap();
+
+        assert_eq!(Some("1.0.0".to_string()), get_active_version());
+
+        engine.add_container(ContainerInfo {
+            id: "abc".to_string(),
+            names: vec!["/debian_bridge_foo".to_string()],
+            image: "debian_bridge_foo".to_string(),
+            labels: HashMap::new(),
+            state: "exited".to_string(),
+        });
+        app.run("foo", &vec![]).unwrap();
+
+        assert_eq!(
+            Some(vec![
+                "start".to_string(),
+                "-ai".to_string(),
+                "abc".to_string()
+            ]),
+            engine.commands().pop()
+        );
+    }
+
+    #[test]
+    fn wayland_display_shared_success() {
+        let mut fixture = Fixture::new("wayland");
+        let socket = fixture.touch("wayland-1");
+
+        fixture.system.wm = Some(WindowManager::Wayland);
+        fixture.system.wayland = Some(socket.to_owned());
+
+        let options = RunOptions {
+            electron: true,
+            ..Default::default()
+        };
+        let command = fixture.run_with(vec![Feature::Display], options);
+        let socket = socket.display().to_string();
+
+        assert!(command.contains(&format!("{}:{}", socket, socket)));
+        assert!(command.contains(&"WAYLAND_DISPLAY=wayland-1".to_string()));
+        assert!(command.contains(&format!("XDG_RUNTIME_DIR={}", fixture.cache_path.display())));
+        assert!(command.contains(&"GDK_BACKEND=wayland,x11".to_string()));
+        assert!(command.contains(&"DISPLAY".to_string()));
+        assert_eq!(
+            vec![
+                "--enable-features=UseOzonePlatform",
+                "--ozone-platform=wayland"
+            ],
+            command[command.len() - 2..].to_vec()
+        );
+    }
+
+    #[test]
+    fn pulse_socket_shared_success() {
+        let mut fixture = Fixture::new("pulse");
+        let socket = fixture.touch("native");
+        let cookie = fixture.touch("cookie");
+
+        fixture.system.sd = Some(SoundDriver::PulseAudio);
+        fixture.system.pulse = Some(socket.to_owned());
+        fixture.system.pulse_cookie = Some(cookie.to_owned());
+        fixture.system.devices = vec![(DeviceClass::Audio, PathBuf::from("/dev/snd"))];
+
+        let command = fixture.run(vec![Feature::Sound]);
+        let socket = socket.display().to_string();
+
+        assert!(command.contains(&format!("{}:{}", socket, socket)));
+        assert!(command.contains(&format!("PULSE_SERVER=unix:{}", socket)));
+        assert!(command.contains(&format!(
+            "{}:/tmp/.debian_bridge-pulse-cookie:ro",
+            cookie.display()
+        )));
+        assert!(!command.contains(&"--device".to_string()));
+    }
+
+    #[test]
+    fn image_user_owns_shared_files_success() {
+        let mut fixture = Fixture::new("image_user");
+        let cookie = fixture.touch("cookie");
+        let (uid, gid) = util::get_user_ids().unwrap();
+
+        fixture.system.sd = Some(SoundDriver::PulseAudio);
+        fixture.system.pulse = Some(fixture.touch("native"));
+        fixture.system.pulse_cookie = Some(cookie.to_owned());
+
+        let command = fixture.run(vec![Feature::Sound, Feature::Notification]);
+        let dockerfile = fixture.engine.dockerfiles().pop().unwrap();
+
+        assert!(dockerfile.contains(&format!(
+            "groupadd -o -g {gid} $informuser && useradd -o -m -u {uid} -g {gid} $informuser",
+            uid = uid,
+            gid = gid
+        )));
+        assert!(dockerfile.contains("USER $informuser"));
+        assert!(command.contains(&format!(
+            "{}:/tmp/.debian_bridge-pulse-cookie:ro",
+            cookie.display()
+        )));
+        assert!(!command.contains(&"--user".to_string()));
+    }
+
+    #[test]
+    fn printing_and_clipboard_validated_success() {
+        let mut fixture = Fixture::new("printing");
+        let socket = fixture.touch("cups.sock");
+
+        fixture.system.wm = Some(WindowManager::X11);
+        fixture.system.cups = Some(socket.to_owned());
+
+        {
+            let mut app = fixture.app();
+
+            assert!(app
+                .features
+                .validate(&vec![Feature::Printing, Feature::Clipboard]));
+            assert!(!app.features.validate(&vec![Feature::Portal]));
+            assert!(fixture.create(&mut app, vec![Feature::Clipboard]).is_err());
+        }
+
+        let command = fixture.run(vec![Feature::Printing]);
+
+        assert!(command.contains(&format!("{}:/run/cups/cups.sock", socket.display())));
+        assert!(command.contains(&"CUPS_SERVER=/run/cups/cups.sock".to_string()));
+    }
+
+    #[test]
+    fn device_features_shared_success() {
+        let mut fixture = Fixture::new("devices");
+
+        fixture.system.devices = vec![
+            (DeviceClass::Camera, PathBuf::from("/dev/video0")),
+            (DeviceClass::Camera, PathBuf::from("/dev/video2")),
+            (DeviceClass::Input, PathBuf::from("/dev/input")),
+        ];
+
+        {
+            let app = fixture.app();
+
+            assert!(app
+                .features
+                .validate(&vec![Feature::Camera, Feature::Input]));
+            assert!(!app.features.validate(&vec![Feature::Gpu]));
+        }
+
+        let command = fixture.run(vec![Feature::Camera]);
+
+        assert_eq!(
+            vec!["/dev/video0", "/dev/video2"],
+            get_values(&command, "--device")
+        );
+        assert_eq!(
+            vec!["c 81:* rmw"],
+            get_values(&command, "--device-cgroup-rule")
+        );
+        assert!(!command.contains(&"--privileged".to_string()));
+
+        let command = fixture.run(vec![Feature::Devices]);
+
+        assert!(command.contains(&"--privileged".to_string()));
+        assert!(command.contains(&"/dev:/dev".to_string()));
+        assert!(!command.contains(&"--device".to_string()));
+    }
+
+    #[test]
+    fn sound_devices_shared_without_socket_success() {
+        let mut fixture = Fixture::new("sound_devices");
+
+        fixture.system.sd = Some(SoundDriver::Alsa);
+
+        let command = fixture.run(vec![Feature::Sound]);
+
+        // no rule for a class that wasn't found
+        assert!(!command.contains(&"--device".to_string()));
+        assert!(!command.contains(&"--device-cgroup-rule".to_string()));
+
+        fixture.system.devices = vec![(DeviceClass::Audio, PathBuf::from("/dev/snd"))];
+
+        let command = fixture.run(vec![Feature::Sound]);
+
+        assert_eq!(vec!["/dev/snd"], get_values(&command, "--device"));
+        assert_eq!(
+            vec!["c 116:* rmw"],
+            get_values(&command, "--device-cgroup-rule")
+        );
+        assert!(!command.contains(&"/dev/snd:/dev/snd".to_string()));
+    }
+
+    #[test]
+    fn ssh_agent_shared_success() {
+        let mut fixture = Fixture::new("ssh_agent");
+        let socket = fixture.touch("agent.sock");
+
+        fixture.system.ssh_agent = Some(socket.to_owned());
+
+        {
+            let app = fixture.app();
+
+            assert!(app.features.validate(&vec![Feature::SshAgent]));
+            assert!(!app.features.validate(&vec![Feature::GpgAgent]));
+        }
+
+        let command = fixture.run(vec![Feature::SshAgent]);
+
+        assert!(command.contains(&format!(
+            "{}:/tmp/.debian_bridge-ssh-agent:ro",
+            socket.display()
+        )));
+        assert!(command.contains(&"SSH_AUTH_SOCK=/tmp/.debian_bridge-ssh-agent".to_string()));
+    }
+
+    #[test]
+    fn gpg_agent_home_writable_success() {
+        let mut fixture = Fixture::new("gpg_agent");
+        let socket = fixture.touch("S.gpg-agent.extra");
+        let home = fixture
+            .cache_path
+            .join(format!("gnupg-{}", std::process::id()));
+
+        fixture.system.gpg_agent = Some(socket.to_owned());
+
+        let command = fixture.run(vec![Feature::GpgAgent]);
+
+        assert!(command.contains(&format!("{}:/tmp/.debian_bridge-gnupg", home.display())));
+        assert!(command.contains(&format!(
+            "{}:/tmp/.debian_bridge-gnupg/S.gpg-agent:ro",
+            socket.display()
+        )));
+        assert!(command.contains(&"GNUPGHOME=/tmp/.debian_bridge-gnupg".to_string()));
+        assert!(!home.exists());
     }
 }
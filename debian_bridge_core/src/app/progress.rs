@@ -0,0 +1,144 @@
+use regex::Regex;
+use serde_json::Value;
+use std::{
+    fmt::{Display, Formatter},
+    sync::Arc,
+};
+
+/// Callback receiving build progress events
+pub type ProgressHandler = Arc<dyn Fn(&BuildProgress) + Send + Sync>;
+
+thread_local! {
+    /// Step line of docker (`Step 1/2 : `) and podman (`STEP 1/2: `) builds
+    static STEP_PATTERN: Regex = Regex::new(r"^(?i:step) (\d+)/(\d+) ?: (.*)$").unwrap();
+}
+
+/// Progress of an image build parsed from docker build output
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildProgress {
+    /// A new Dockerfile instruction started
+    Step {
+        current: usize,
+        total: usize,
+        instruction: String,
+    },
+    /// Download or extraction state of a layer
+    Layer {
+        id: String,
+        status: String,
+        current: Option<u64>,
+        total: Option<u64>,
+    },
+    /// Any other output line
+    Output(String),
+}
+
+impl BuildProgress {
+    pub fn parse(output: &Value) -> Option<Self> {
+        if let Some(stream) = output.get("stream").and_then(Value::as_str) {
+            let stream = stream.trim_end();
+
+            if stream.is_empty() {
+                return None;
+            }
+
+            return Some(STEP_PATTERN.with(|pattern| match pattern.captures(stream) {
+                Some(caps) => BuildProgress::Step {
+                    current: caps[1].parse().unwrap_or(0),
+                    total: caps[2].parse().unwrap_or(0),
+                    instruction: caps[3].to_string(),
+                },
+                None => BuildProgress::Output(stream.to_string()),
+            }));
+        }
+
+        let status = output.get("status").and_then(Value::as_str)?.to_string();
+        let detail = output.get("progressDetail");
+
+        Some(match output.get("id").and_then(Value::as_str) {
+            Some(id) => BuildProgress::Layer {
+                id: id.to_string(),
+                status,
+                current: detail
+                    .and_then(|d| d.get("current"))
+                    .and_then(Value::as_u64),
+                total: detail.and_then(|d| d.get("total")).and_then(Value::as_u64),
+            },
+            None => BuildProgress::Output(status),
+        })
+    }
+}
+
+impl Display for BuildProgress {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            BuildProgress::Step {
+                current,
+                total,
+                instruction,
+            } => write!(f, "Step {}/{} : {}", current, total, instruction),
+            BuildProgress::Layer {
+                id,
+                status,
+                current: Some(current),
+                total: Some(total),
+            } => write!(f, "{}: {} {}/{}", id, status, current, total),
+            BuildProgress::Layer { id, status, .. } => write!(f, "{}: {}", id, status),
+            BuildProgress::Output(output) => write!(f, "{}", output),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_parses_success() {
+        let step = serde_json::json!({ "stream": "Step 3/12 : RUN apt-get update\n" });
+        let layer = serde_json::json!({
+            "status": "Downloading",
+            "progressDetail": { "current": 1024, "total": 4096 },
+            "progress": "[=>    ]",
+            "id": "a1b2c3"
+        });
+        let podman_step = serde_json::json!({ "stream": "STEP 4/12: RUN apt-get update\n" });
+        let output = serde_json::json!({ "stream": " ---> Running in 0123456789ab\n" });
+
+        assert_eq!(
+            BuildProgress::parse(&step),
+            Some(BuildProgress::Step {
+                current: 3,
+                total: 12,
+                instruction: "RUN apt-get update".to_string(),
+            })
+        );
+        assert_eq!(
+            BuildProgress::parse(&podman_step),
+            Some(BuildProgress::Step {
+                current: 4,
+                total: 12,
+                instruction: "RUN apt-get update".to_string(),
+            })
+        );
+        assert_eq!(
+            BuildProgress::parse(&layer),
+            Some(BuildProgress::Layer {
+                id: "a1b2c3".to_string(),
+                status: "Downloading".to_string(),
+                current: Some(1024),
+                total: Some(4096),
+            })
+        );
+        assert_eq!(
+            BuildProgress::parse(&output),
+            Some(BuildProgress::Output(
+                " ---> Running in 0123456789ab".to_string()
+            ))
+        );
+        assert_eq!(
+            BuildProgress::parse(&serde_json::json!({ "stream": "\n" })),
+            None
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use super::error::AppError;
+
+type AppResult<T> = Result<T, AppError>;
+type Rollback<'a, S> = Box<dyn FnOnce(&mut S) -> AppResult<()> + 'a>;
+
+/// Sequence of named steps over a state. When a step fails, rollbacks of all previous
+/// steps are applied in reverse order and the error names the failed step
+pub struct Transaction<'a, S> {
+    rollbacks: Vec<(String, Rollback<'a, S>)>,
+}
+
+impl<'a, S> Transaction<'a, S> {
+    pub fn new() -> Self {
+        Transaction { rollbacks: vec![] }
+    }
+
+    pub fn step<T, F, R, N>(
+        &mut self,
+        state: &mut S,
+        name: N,
+        action: F,
+        rollback: R,
+    ) -> AppResult<T>
+    where
+        F: FnOnce(&mut S) -> AppResult<T>,
+        R: FnOnce(&mut S) -> AppResult<()> + 'a,
+        N: Into<String>,
+    {
+        let name = name.into();
+
+        debug!("Transaction step: {}", name);
+
+        match action(state) {
+            Ok(value) => {
+                self.rollbacks.push((name, Box::new(rollback)));
+                Ok(value)
+            }
+            Err(err) => {
+                self.rollback(state);
+                Err(AppError::Step(name, Box::new(err)))
+            }
+        }
+    }
+
+    pub fn commit(mut self) {
+        self.rollbacks.clear();
+    }
+
+    fn rollback(&mut self, state: &mut S) {
+        while let Some((name, rollback)) = self.rollbacks.pop() {
+            match rollback(state) {
+                Ok(_) => debug!("Rolled back: {}", name),
+                Err(err) => error!("Can't roll back '{}': {}", name, err.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transaction_rolls_back_in_reverse_order() {
+        let mut state: Vec<&str> = vec![];
+        let mut transaction = Transaction::new();
+
+        transaction
+            .step(
+                &mut state,
+                "first",
+                |s| Ok(s.push("first")),
+                |s| {
+                    s.push("first rolled back");
+                    Ok(())
+                },
+            )
+            .unwrap();
+        transaction
+            .step(
+                &mut state,
+                "second",
+                |s| Ok(s.push("second")),
+                |s| {
+                    s.push("second rolled back");
+                    Ok(())
+                },
+            )
+            .unwrap();
+
+        let result = transaction.step(
+            &mut state,
+            "third",
+            |_| Err::<(), _>(AppError::Docker),
+            |_| Ok(()),
+        );
+
+        match result {
+            Err(AppError::Step(name, _)) => assert_eq!("third", name),
+            _ => panic!("Failed step expected"),
+        }
+        assert_eq!(
+            state,
+            vec!["first", "second", "second rolled back", "first rolled back"]
+        );
+    }
+}
@@ -1,21 +1,108 @@
 use super::{deb::Deb, Program};
-use dockerfile::{Cmd, Copy, Dockerfile, Env, Run, User, Workdir};
+use dockerfile::{Cmd, Copy, Dockerfile, Env, Label, Run, User, Workdir};
 use freedesktop_desktop_entry::{Application, DesktopEntry, DesktopType};
-use std::path::Path;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use std::path::{Path, PathBuf};
 
 use crate::{app::error::AppError, Feature};
 #[cfg(test)]
 use mocktopus::macros::*;
-use std::process::{Command, Stdio};
+use std::{
+    ffi::OsString,
+    os::unix::{ffi::OsStringExt, fs::MetadataExt},
+    process::{Command, Stdio},
+};
 
 type AppResult<T> = Result<T, AppError>;
 
+/// Name of a current user, `USER` isn't set in some sessions (e.g. services)
 #[cfg_attr(test, mockable)]
 fn get_user() -> Option<String> {
-    std::env::var_os("USER")?
-        .as_os_str()
-        .to_str()
-        .map(|s| s.to_string())
+    if let Ok(user) = std::env::var("USER") {
+        return Some(user);
+    }
+
+    Command::new("id")
+        .arg("-un")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|user| user.trim().to_string())
+}
+
+/// User and group ids of a current process
+#[cfg_attr(test, mockable)]
+pub fn get_user_ids() -> Option<(u32, u32)> {
+    std::fs::metadata("/proc/self")
+        .ok()
+        .map(|meta| (meta.uid(), meta.gid()))
+}
+
+/// Home directory of a user created in a program image
+pub fn get_container_home() -> AppResult<PathBuf> {
+    let user = get_user().ok_or(AppError::Program("Can not find a current user".into()))?;
+
+    Ok(Path::new("/home").join(user))
+}
+
+/// PulseAudio socket from `PULSE_SERVER`, or the socket in `XDG_RUNTIME_DIR` also served
+/// by pipewire-pulse
+pub fn get_pulse_socket() -> Option<PathBuf> {
+    let socket = match std::env::var("PULSE_SERVER") {
+        Ok(server) => PathBuf::from(server.trim_start_matches("unix:")),
+        Err(_) => Path::new(&std::env::var_os("XDG_RUNTIME_DIR")?).join("pulse/native"),
+    };
+
+    Some(socket).filter(|socket| socket.is_absolute() && socket.exists())
+}
+
+/// Public keyring of the host GnuPG home, `GNUPGHOME` or `~/.gnupg`
+pub fn get_gpg_keyring() -> Option<PathBuf> {
+    let home = match std::env::var_os("GNUPGHOME") {
+        Some(home) => PathBuf::from(home),
+        None => dirs::home_dir()?.join(".gnupg"),
+    };
+
+    Some(home.join("pubring.kbx")).filter(|keyring| keyring.exists())
+}
+
+/// Cookie authorizing PulseAudio clients, `PULSE_COOKIE` or the default cookie files
+pub fn get_pulse_cookie() -> Option<PathBuf> {
+    if let Some(cookie) = std::env::var_os("PULSE_COOKIE") {
+        return Some(PathBuf::from(cookie)).filter(|cookie| cookie.exists());
+    }
+
+    let home = dirs::home_dir()?;
+
+    vec![
+        dirs::config_dir()?.join("pulse/cookie"),
+        home.join(".pulse-cookie"),
+    ]
+    .into_iter()
+    .find(|cookie| cookie.exists())
+}
+
+/// PipeWire socket from `PIPEWIRE_REMOTE` or `XDG_RUNTIME_DIR`
+pub fn get_pipewire_socket() -> Option<PathBuf> {
+    let remote = PathBuf::from(std::env::var_os("PIPEWIRE_REMOTE").unwrap_or("pipewire-0".into()));
+    let socket = match remote.is_absolute() {
+        true => remote,
+        false => Path::new(&std::env::var_os("XDG_RUNTIME_DIR")?).join(remote),
+    };
+
+    Some(socket).filter(|socket| socket.exists())
+}
+
+/// Socket of a wayland compositor from `WAYLAND_DISPLAY`, relative to `XDG_RUNTIME_DIR`
+pub fn get_wayland_socket() -> Option<PathBuf> {
+    let display = PathBuf::from(std::env::var_os("WAYLAND_DISPLAY")?);
+    let socket = match display.is_absolute() {
+        true => display,
+        false => Path::new(&std::env::var_os("XDG_RUNTIME_DIR")?).join(display),
+    };
+
+    Some(socket).filter(|socket| socket.exists())
 }
 
 #[cfg_attr(test, mockable)]
@@ -44,8 +131,136 @@ fn is_gnome_terminal() -> bool {
         .is_ok()
 }
 
-pub fn gen_dockerfile(deb: &Deb, program: &Program) -> AppResult<String> {
-    let mut dockerfile = Dockerfile::base("debian:9-slim")
+const ARGUMENTS_MOUNT_PATH: &str = "/mnt/debian_bridge";
+const FILE_SCHEME: &str = "file://";
+/// Characters escaped in paths of `file://` URIs
+const URI_PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+pub const BASE_IMAGE: &str = "debian:9-slim";
+pub const XDG_OPEN_FILE: &str = "xdg-open";
+/// `xdg-open` replacement opening links with the host browser through the OpenURI portal
+pub const XDG_OPEN_SCRIPT: &str = "#!/bin/sh\nexec gdbus call --session --dest \
+                                   org.freedesktop.portal.Desktop \\\n--object-path \
+                                   /org/freedesktop/portal/desktop \\\n--method \
+                                   org.freedesktop.portal.OpenURI.OpenURI \"\" \"$1\" {}\n";
+
+/// Rewrites host paths and `file://` URIs in program arguments to container paths, URIs
+/// are decoded and encoded again. Returns rewritten arguments and volumes required to
+/// reach them
+pub fn forward_arguments(arguments: &Vec<String>) -> (Vec<String>, Vec<String>) {
+    let mut volumes = vec![];
+    let arguments = arguments
+        .iter()
+        .enumerate()
+        .map(|(idx, argument)| {
+            let (is_uri, path) = if argument.starts_with(FILE_SCHEME) {
+                let path = percent_decode_str(&argument[FILE_SCHEME.len()..]).collect();
+
+                (true, PathBuf::from(OsString::from_vec(path)))
+            } else {
+                (false, PathBuf::from(argument))
+            };
+
+            let path = match std::fs::canonicalize(path) {
+                Ok(path) => path,
+                Err(_) => return argument.to_owned(),
+            };
+
+            let (host_path, file_name) = match (path.to_str(), path.file_name()) {
+                (Some(host_path), Some(file_name)) if !host_path.contains(':') => {
+                    (host_path, file_name.to_string_lossy())
+                }
+                _ => {
+                    warn!("Can not mount '{}', passing it as is", argument);
+                    return argument.to_owned();
+                }
+            };
+
+            let container_path = format!("{}/{}/{}", ARGUMENTS_MOUNT_PATH, idx, file_name);
+
+            volumes.push(format!("{}:{}", host_path, container_path));
+
+            match is_uri {
+                true => format!(
+                    "{}{}",
+                    FILE_SCHEME,
+                    utf8_percent_encode(&container_path, URI_PATH)
+                ),
+                false => container_path,
+            }
+        })
+        .collect();
+
+    (arguments, volumes)
+}
+
+/// Replaces characters not allowed in docker tags
+pub fn sanitize_tag_version(version: &str) -> String {
+    let version: String = version
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '.' | '-' => c,
+            _ => '_',
+        })
+        .take(100)
+        .collect();
+
+    version
+        .trim_start_matches(|c| c == '.' || c == '-')
+        .to_string()
+}
+
+/// Generates an image tag from a package version and a build timestamp
+pub fn gen_image_tag(version: &Option<String>, timestamp: u64) -> String {
+    format!(
+        "{}-{}",
+        sanitize_tag_version(version.as_ref().map_or("unknown", String::as_str)),
+        timestamp
+    )
+}
+
+/// Extracts a sanitized version and a build timestamp from a tag generated with `gen_image_tag`
+pub fn parse_image_tag(tag: &str) -> Option<(String, u64)> {
+    let mut parts = tag.rsplitn(2, '-');
+    let timestamp = parts.next()?.parse().ok()?;
+
+    Some((parts.next()?.to_string(), timestamp))
+}
+
+/// Calculates a sha256 checksum of a file
+pub fn get_file_hash(path: &Path) -> AppResult<String> {
+    let output = Command::new("sha256sum")
+        .arg(path)
+        .output()
+        .map_err(|err| AppError::File(err.to_string()))?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .next()
+        .filter(|_| output.status.success())
+        .map(|hash| hash.to_string())
+        .ok_or(AppError::File(format!(
+            "Can not calculate a checksum of '{}'",
+            path.display()
+        )))
+}
+
+pub fn gen_dockerfile(
+    deb: &Deb,
+    program: &Program,
+    labels: &Vec<(String, String)>,
+) -> AppResult<String> {
+    let (uid, gid) = get_user_ids().ok_or(AppError::Program("Can not find user ids".into()))?;
+    let mut dockerfile = Dockerfile::base(BASE_IMAGE)
         .push(Env::new(format!(
             "informuser={}",
             get_user().ok_or(AppError::Program("Can not find a current user".into()))?
@@ -65,12 +280,38 @@ pub fn gen_dockerfile(deb: &Deb, program: &Program) -> AppResult<String> {
         dockerfile = dockerfile.push(Run::new(format!("apt-get install -y {}", d)));
     }
 
+    if program.settings.contains(&Feature::Portal) {
+        dockerfile = dockerfile
+            .push(Copy::new(format!(
+                "{} /usr/local/bin/{}",
+                XDG_OPEN_FILE, XDG_OPEN_FILE
+            )))
+            .push(Run::new(format!(
+                "apt-get install -y --no-install-recommends libglib2.0-bin && chmod +x \
+                 /usr/local/bin/{}",
+                XDG_OPEN_FILE
+            )));
+    }
+
+    if !labels.is_empty() {
+        dockerfile = dockerfile.push(Label::new(
+            labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, value.replace('"', "\\\"")))
+                .collect::<Vec<String>>()
+                .join(" "),
+        ));
+    }
+
     Ok(dockerfile
         .push(Run::new("dpkg -i /data/application.deb || true"))
-        .push(Run::new(
+        // the user owns host files shared with programs, e.g. sockets and cookies
+        .push(Run::new(format!(
             "apt-get install -y -f --no-install-recommends && rm -rf /var/lib/apt/lists/* && \
-             useradd $informuser",
-        ))
+             groupadd -o -g {gid} $informuser && useradd -o -m -u {uid} -g {gid} $informuser",
+            uid = uid,
+            gid = gid
+        )))
         .push(User::new("$informuser"))
         .push(Env::new("HOME /home/$informuser"))
         .push(Cmd::new(program.command.to_owned()))
@@ -93,7 +334,7 @@ pub fn gen_desktop_entry<T: Into<String>, S: Into<String>, U: Into<String>>(
     let package_name = package_name.into();
     let name = name.into();
     let exec = format!(
-        "gnome-terminal -e '{} run {}'",
+        "gnome-terminal -- {} run {} %U",
         get_package_path(package_name.as_str())?,
         name
     );
@@ -126,8 +367,9 @@ mod tests {
     fn test_gen_dockerfile() {
         Dependencies::extract.mock_safe(|_| MockResult::Return("foo bar".to_string()));
         get_user.mock_safe(|| MockResult::Return(Some("user".to_string())));
+        get_user_ids.mock_safe(|| MockResult::Return(Some((1001, 1002))));
 
-        let dockerfile = gen_dockerfile(&get_deb(), &get_program()).unwrap();
+        let dockerfile = gen_dockerfile(&get_deb(), &get_program(), &vec![]).unwrap();
 
         assert_eq!(
             dockerfile,
@@ -141,7 +383,8 @@ mod tests {
              RUN apt-get install -y baz qux\n\
              RUN dpkg -i /data/application.deb || true\n\
              RUN apt-get install -y -f --no-install-recommends && rm -rf /var/lib/apt/lists/* && \
-             useradd $informuser\nUSER $informuser\nENV HOME /home/$informuser\nCMD foobar\n"
+             groupadd -o -g 1002 $informuser && useradd -o -m -u 1001 -g 1002 $informuser\n\
+             USER $informuser\nENV HOME /home/$informuser\nCMD foobar\n"
         )
     }
 
@@ -165,10 +408,92 @@ mod tests {
             Comment=bar\n\
             Categories=GNOME;GTK\n\
             Keywords=\"Foo;\"\n\
-            Exec=gnome-terminal -e \'/foo run Foo\'\n"
+            Exec=gnome-terminal -- /foo run Foo %U\n"
         )
     }
 
+    #[test]
+    fn test_forward_arguments() {
+        let mut file = std::env::temp_dir();
+        file.push("debian_bridge_forward_arguments.txt");
+        std::fs::write(&file, "").unwrap();
+
+        let file = std::fs::canonicalize(file).unwrap();
+        let file_str = file.to_str().unwrap().to_string();
+        let (arguments, volumes) = forward_arguments(&vec![
+            "--foo".to_string(),
+            file_str.to_owned(),
+            format!("file://{}", file_str),
+            "/non/existent/path".to_string(),
+        ]);
+
+        std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(
+            arguments,
+            vec![
+                "--foo",
+                "/mnt/debian_bridge/1/debian_bridge_forward_arguments.txt",
+                "file:///mnt/debian_bridge/2/debian_bridge_forward_arguments.txt",
+                "/non/existent/path",
+            ]
+        );
+        assert_eq!(
+            volumes,
+            vec![
+                format!(
+                    "{}:/mnt/debian_bridge/1/debian_bridge_forward_arguments.txt",
+                    file_str
+                ),
+                format!(
+                    "{}:/mnt/debian_bridge/2/debian_bridge_forward_arguments.txt",
+                    file_str
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_forward_encoded_uri() {
+        let mut file = std::env::temp_dir();
+        file.push("debian_bridge My Doc é.txt");
+        std::fs::write(&file, "").unwrap();
+
+        let file = std::fs::canonicalize(file).unwrap();
+        let file_str = file.to_str().unwrap().to_string();
+        let (arguments, volumes) = forward_arguments(&vec![format!(
+            "file://{}",
+            file_str.replace(' ', "%20").replace('é', "%C3%A9")
+        )]);
+
+        std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(
+            arguments,
+            vec!["file:///mnt/debian_bridge/0/debian_bridge%20My%20Doc%20%C3%A9.txt"]
+        );
+        assert_eq!(
+            volumes,
+            vec![format!(
+                "{}:/mnt/debian_bridge/0/debian_bridge My Doc é.txt",
+                file_str
+            )]
+        );
+    }
+
+    #[test]
+    fn test_gen_image_tag() {
+        let tag = gen_image_tag(&Some("1:2.15.3~beta+dfsg-1".to_string()), 1571000000);
+
+        assert_eq!(tag, "1_2.15.3_beta_dfsg-1-1571000000");
+        assert_eq!(
+            parse_image_tag(&tag),
+            Some(("1_2.15.3_beta_dfsg-1".to_string(), 1571000000))
+        );
+        assert_eq!(gen_image_tag(&None, 1), "unknown-1");
+        assert_eq!(parse_image_tag("latest"), None);
+    }
+
     fn get_program() -> Program {
         Program::new(
             "foobar".to_string(),
@@ -177,6 +502,7 @@ mod tests {
             &None,
             &None,
             &Some("baz qux".to_string()),
+            &Default::default(),
         )
     }
 
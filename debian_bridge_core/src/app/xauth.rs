@@ -0,0 +1,121 @@
+use super::error::AppError;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+type AppResult<T> = Result<T, AppError>;
+
+pub const XAUTH_PREFIX: &str = "xauth-";
+pub const XAUTH_DIR: &str = "xauth";
+pub const XAUTH_FILE: &str = "Xauthority";
+/// Address family of xauth entries matching any host, so a container hostname doesn't matter
+const FAMILY_WILD: &str = "ffff";
+
+/// Xauthority file with the cookie of a display, readable by X11 clients in a container.
+/// The directory of the file is meant to be mounted, so a persistent container finds a fresh
+/// file on every start. The file is removed when dropped, the directory too if it's empty
+pub struct XAuthority {
+    dir: PathBuf,
+}
+
+impl XAuthority {
+    pub fn try_new(dir: &Path, display: &str) -> AppResult<Self> {
+        let output = Command::new("xauth")
+            .args(&["nlist", display])
+            .output()
+            .map_err(|err| AppError::Program(format!("Can not run xauth: {}", err)))?;
+        let entries = to_wildcard(&String::from_utf8_lossy(&output.stdout));
+
+        if entries.is_empty() {
+            return Err(AppError::Program(format!(
+                "Can not find a cookie of display '{}'",
+                display
+            )));
+        }
+
+        std::fs::create_dir_all(dir).map_err(|err| AppError::File(err.to_string()))?;
+
+        let xauth = XAuthority {
+            dir: dir.to_owned(),
+        };
+        let path = xauth.path();
+
+        if path.exists() {
+            std::fs::remove_file(&path).map_err(|err| AppError::File(err.to_string()))?;
+        }
+
+        let mut child = Command::new("xauth")
+            .arg("-f")
+            .arg(&path)
+            .args(&["nmerge", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|err| AppError::Program(format!("Can not run xauth: {}", err)))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(entries.as_bytes())
+                .map_err(|err| AppError::File(err.to_string()))?;
+        }
+
+        match child.wait() {
+            Ok(status) if status.success() => Ok(xauth),
+            _ => Err(AppError::Program(format!(
+                "Can not write '{}'",
+                path.display()
+            ))),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        self.dir.as_path()
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(XAUTH_FILE)
+    }
+}
+
+impl Drop for XAuthority {
+    fn drop(&mut self) {
+        let path = self.path();
+
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap_or_else(|err| {
+                warn!("Can't remove '{}': {}", path.display(), err.to_string());
+            });
+        }
+
+        std::fs::remove_dir(&self.dir).unwrap_or_default();
+    }
+}
+
+/// Rewrites `xauth nlist` entries to the wildcard family
+fn to_wildcard(nlist: &str) -> String {
+    nlist
+        .lines()
+        .filter(|line| line.len() > FAMILY_WILD.len())
+        .map(|line| format!("{}{}\n", FAMILY_WILD, &line[FAMILY_WILD.len()..]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cookie_rewritten_to_wildcard_success() {
+        let nlist = "0100 0004 686f7374 0001 30 0012 4d49542d4d414749432d434f4f4b49452d31 0010 \
+                     00112233445566778899aabbccddeeff\n\n";
+
+        assert_eq!(
+            "ffff 0004 686f7374 0001 30 0012 4d49542d4d414749432d434f4f4b49452d31 0010 \
+             00112233445566778899aabbccddeeff\n",
+            to_wildcard(nlist)
+        );
+        assert!(to_wildcard("").is_empty());
+    }
+}
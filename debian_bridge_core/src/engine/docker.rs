@@ -0,0 +1,277 @@
+use super::{AppResult, BuildOutput, ContainerInfo, Engine, ImageInfo};
+use crate::{app::error::AppError, Executor};
+use shiplift::{BuildOptions, ContainerListOptions, Docker, ImageListOptions, PullOptions};
+use std::{
+    fs::File,
+    io::Write,
+    path::Path,
+    process::{Command, ExitStatus, Output, Stdio},
+};
+use tokio::prelude::{Future, Stream};
+
+/// Engine talking to a docker API socket, interactive commands use the engine command line
+pub struct DockerEngine {
+    docker: Docker,
+    executor: Executor,
+    cli: String,
+    endpoint: Option<String>,
+    env: Vec<(&'static str, String)>,
+}
+
+impl DockerEngine {
+    pub fn new(docker: Docker, executor: &Executor) -> Self {
+        DockerEngine {
+            docker,
+            executor: executor.to_owned(),
+            cli: "docker".to_string(),
+            endpoint: None,
+            env: vec![],
+        }
+    }
+
+    /// Sets a command line tool compatible with docker arguments
+    pub fn with_cli<T: Into<String>>(mut self, cli: T) -> Self {
+        self.cli = cli.into();
+        self
+    }
+
+    /// Sets a daemon address shown to users
+    pub fn with_endpoint<T: Into<String>>(mut self, endpoint: T) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Sets variables of command line calls, e.g. `DOCKER_HOST` of the API client daemon
+    pub fn with_env(mut self, env: Vec<(&'static str, String)>) -> Self {
+        self.env = env;
+        self
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.cli);
+
+        command.envs(self.env.iter().map(|(name, value)| (*name, value)));
+        command
+    }
+}
+
+impl Engine for DockerEngine {
+    fn name(&self) -> String {
+        match self.cli.as_str() {
+            "docker" => "Docker".to_string(),
+            cli => format!("{} (docker API)", cli),
+        }
+    }
+
+    fn endpoint(&self) -> Option<String> {
+        self.endpoint.to_owned()
+    }
+
+    fn version(&self) -> AppResult<String> {
+        self.executor
+            .wait(self.docker.version())
+            .map(|version| version.api_version)
+            .map_err(|_| AppError::Docker)
+    }
+
+    fn build(
+        &self,
+        context: &Path,
+        tag: &str,
+        nocache: bool,
+        mut output: BuildOutput,
+    ) -> AppResult<()> {
+        let fut = self
+            .docker
+            .images()
+            .build(
+                &BuildOptions::builder(context.as_os_str().to_str().unwrap())
+                    .tag(tag)
+                    .nocache(nocache)
+                    .build(),
+            )
+            .for_each(move |chunk| {
+                output(&chunk).map_err(|err| shiplift::Error::InvalidResponse(err.to_string()))
+            });
+
+        self.executor.wait(fut).map_err(|err| {
+            error!("{}", err.to_string());
+            AppError::Docker
+        })
+    }
+
+    fn pull(&self, image: &str) -> AppResult<()> {
+        let mut parts = image.splitn(2, ':');
+        let name = parts.next().unwrap_or_default();
+        let tag = parts.next().unwrap_or("latest");
+        let fut = self
+            .docker
+            .images()
+            .pull(&PullOptions::builder().image(name).tag(tag).build())
+            .for_each(|chunk| {
+                debug!("{}", chunk);
+                Ok(())
+            });
+
+        self.executor.wait(fut).map_err(|err| {
+            error!("{}", err.to_string());
+            AppError::Docker
+        })
+    }
+
+    fn tag(&self, source: &str, target: &str) -> AppResult<()> {
+        let status = self
+            .command()
+            .args(&["tag", source, target])
+            .status()
+            .map_err(|_| AppError::Docker)?;
+
+        if !status.success() {
+            return Err(AppError::Program(format!(
+                "Can not tag '{}' as '{}'",
+                source, target
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn images(&self) -> AppResult<Vec<ImageInfo>> {
+        let fut = self
+            .docker
+            .images()
+            .list(&ImageListOptions::builder().build())
+            .map(|images| {
+                images
+                    .into_iter()
+                    .map(|image| ImageInfo {
+                        id: image.id,
+                        tags: image
+                            .repo_tags
+                            .unwrap_or_default()
+                            .into_iter()
+                            .filter(|tag| tag != "<none>:<none>")
+                            .collect(),
+                        labels: image.labels.unwrap_or_default(),
+                    })
+                    .collect()
+            });
+
+        self.executor.wait(fut).map_err(|err| {
+            warn!("{}", err.to_string());
+            AppError::Docker
+        })
+    }
+
+    fn delete_image(&self, name: &str) -> AppResult<()> {
+        let fut = self.docker.images().get(name).delete();
+
+        self.executor.wait(fut).map(|_| ()).map_err(|err| {
+            warn!("{}", err.to_string());
+
+            match err {
+                shiplift::Error::Fault { code, .. } => AppError::DockerStatus(code.as_u16() as i16),
+                _ => AppError::Docker,
+            }
+        })
+    }
+
+    fn containers(&self, all: bool) -> AppResult<Vec<ContainerInfo>> {
+        let mut options = ContainerListOptions::builder();
+
+        if all {
+            options.all();
+        }
+
+        let fut = self
+            .docker
+            .containers()
+            .list(&options.build())
+            .map(|containers| {
+                containers
+                    .into_iter()
+                    .map(|c| ContainerInfo {
+                        id: c.id,
+                        names: c.names,
+                        image: c.image,
+                        labels: c.labels,
+                        state: c.state,
+                    })
+                    .collect()
+            });
+
+        self.executor.wait(fut).map_err(|_| AppError::Docker)
+    }
+
+    fn delete_container(&self, id: &str) -> AppResult<()> {
+        let fut = self.docker.containers().get(id).delete();
+
+        self.executor.wait(fut).map_err(|err| {
+            warn!("{}", err.to_string());
+            AppError::Docker
+        })
+    }
+
+    fn export(&self, image: &str, path: &Path) -> AppResult<()> {
+        let mut file = File::create(path).map_err(|err| AppError::File(err.to_string()))?;
+        let fut = self
+            .docker
+            .images()
+            .get(image)
+            .export()
+            .for_each(move |chunk| {
+                file.write_all(&chunk)
+                    .map_err(|err| shiplift::Error::InvalidResponse(err.to_string()))
+            });
+
+        self.executor.wait(fut).map_err(|err| {
+            error!("{}", err.to_string());
+            AppError::Docker
+        })
+    }
+
+    fn import(&self, path: &Path) -> AppResult<()> {
+        let file = File::open(path).map_err(|err| AppError::File(err.to_string()))?;
+        let fut = self
+            .docker
+            .images()
+            .import(Box::new(file))
+            .for_each(|chunk| {
+                if let Some(error) = chunk.get("error") {
+                    error!("Docker output: {}", error);
+                    return Err(shiplift::Error::InvalidResponse(
+                        "Failed to import an image".to_string(),
+                    ));
+                }
+
+                debug!("{}", chunk);
+                Ok(())
+            });
+
+        self.executor.wait(fut).map_err(|err| {
+            error!("{}", err.to_string());
+            AppError::Docker
+        })
+    }
+
+    fn run(&self, args: &Vec<String>) -> AppResult<ExitStatus> {
+        debug!("{} arguments: {:?}", self.cli, args);
+
+        self.command()
+            .args(args)
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .status()
+            .map_err(|err| {
+                error!("{}", err.to_string());
+                AppError::Docker
+            })
+    }
+
+    fn output(&self, args: &Vec<String>) -> AppResult<Output> {
+        self.command()
+            .args(args)
+            .output()
+            .map_err(|_| AppError::Docker)
+    }
+}
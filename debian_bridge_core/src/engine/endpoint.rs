@@ -0,0 +1,233 @@
+use super::AppResult;
+use crate::app::error::AppError;
+use hyper::Uri;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const SYSTEM_SOCKET: &str = "/var/run/docker.sock";
+const UNIX_SCHEME: &str = "unix://";
+const TCP_SCHEME: &str = "tcp://";
+
+/// Docker daemon address. When host isn't set `DOCKER_HOST` is used, then the system socket
+/// and the rootless docker socket
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Endpoint {
+    /// Socket path, `unix://` or `tcp://` address
+    #[serde(default)]
+    pub host: Option<String>,
+    /// Directory with `ca.pem`, `cert.pem` and `key.pem` for TLS connections
+    #[serde(default)]
+    pub cert_path: Option<PathBuf>,
+    /// Verify the daemon certificate with `ca.pem`
+    #[serde(default = "default_tls_verify")]
+    pub tls_verify: bool,
+}
+
+fn default_tls_verify() -> bool {
+    true
+}
+
+impl Default for Endpoint {
+    fn default() -> Self {
+        Endpoint {
+            host: None,
+            cert_path: None,
+            tls_verify: default_tls_verify(),
+        }
+    }
+}
+
+/// Resolved daemon address
+#[derive(Clone, Debug, PartialEq)]
+pub struct Address {
+    pub host: String,
+    pub rootless: bool,
+    pub cert_path: Option<PathBuf>,
+    pub tls_verify: bool,
+}
+
+impl Address {
+    /// Socket path of a `unix://` address
+    pub fn socket(&self) -> Option<&str> {
+        match self.host.starts_with(UNIX_SCHEME) {
+            true => Some(&self.host[UNIX_SCHEME.len()..]),
+            false => None,
+        }
+    }
+
+    pub fn uri(&self) -> AppResult<Uri> {
+        self.host
+            .parse()
+            .map_err(|_| AppError::Program(format!("Invalid docker host '{}'", self.host)))
+    }
+
+    /// `DOCKER_*` variables of the address, so the docker command line uses the same daemon
+    /// as the API client
+    pub fn env(&self) -> Vec<(&'static str, String)> {
+        let mut env = vec![("DOCKER_HOST", self.host.to_owned())];
+
+        env.extend(self.tls_env());
+        env
+    }
+
+    /// TLS variables of the address. The docker API client reads them from the process
+    /// environment only, an application exports them before the runtime starts
+    pub fn tls_env(&self) -> Vec<(&'static str, String)> {
+        match &self.cert_path {
+            Some(cert_path) => vec![
+                ("DOCKER_CERT_PATH", cert_path.to_string_lossy().to_string()),
+                (
+                    "DOCKER_TLS_VERIFY",
+                    match self.tls_verify {
+                        true => "1".to_string(),
+                        false => String::new(),
+                    },
+                ),
+            ],
+            None => vec![],
+        }
+    }
+}
+
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.rootless {
+            true => write!(f, "{} (rootless)", self.host),
+            false => write!(f, "{}", self.host),
+        }
+    }
+}
+
+impl Endpoint {
+    pub fn new<T: Into<String>>(host: T) -> Self {
+        Endpoint {
+            host: Some(host.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Resolves the daemon address and checks TLS files
+    pub fn resolve(&self) -> AppResult<Address> {
+        let host = self.host.to_owned().or(std::env::var("DOCKER_HOST")
+            .ok()
+            .filter(|host| !host.is_empty()));
+        let host = match host {
+            Some(host) => parse_host(&host)?,
+            None => match get_rootless_socket().filter(|socket| socket.exists()) {
+                Some(socket) if !Path::new(SYSTEM_SOCKET).exists() => {
+                    format!("{}{}", UNIX_SCHEME, socket.display())
+                }
+                _ => format!("{}{}", UNIX_SCHEME, SYSTEM_SOCKET),
+            },
+        };
+        let rootless = get_rootless_socket().map_or(false, |socket| {
+            host == format!("{}{}", UNIX_SCHEME, socket.display())
+        });
+
+        if let Some(cert_path) = &self.cert_path {
+            check_certs(cert_path, self.tls_verify)?;
+        }
+
+        Ok(Address {
+            host,
+            rootless,
+            cert_path: self.cert_path.to_owned(),
+            tls_verify: self.tls_verify,
+        })
+    }
+}
+
+fn parse_host(host: &str) -> AppResult<String> {
+    if host.starts_with('/') {
+        return Ok(format!("{}{}", UNIX_SCHEME, host));
+    }
+
+    match host.starts_with(UNIX_SCHEME) || host.starts_with(TCP_SCHEME) {
+        true => Ok(host.to_string()),
+        false => Err(AppError::Program(format!(
+            "Unsupported docker host '{}', use a socket path, unix:// or tcp:// address",
+            host
+        ))),
+    }
+}
+
+fn check_certs(cert_path: &Path, verify: bool) -> AppResult<()> {
+    let mut files = vec!["cert.pem", "key.pem"];
+
+    if verify {
+        files.push("ca.pem");
+    }
+
+    match files.iter().find(|file| !cert_path.join(file).exists()) {
+        Some(file) => Err(AppError::File(format!(
+            "TLS file '{}' not found",
+            cert_path.join(file).display()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Socket of rootless docker, see https://docs.docker.com/engine/security/rootless/
+fn get_rootless_socket() -> Option<PathBuf> {
+    std::env::var_os("XDG_RUNTIME_DIR").map(|dir| Path::new(&dir).join("docker.sock"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoint_host_parses_success() {
+        assert_eq!(
+            "unix:///run/user/1000/docker.sock",
+            parse_host("/run/user/1000/docker.sock").unwrap()
+        );
+        assert_eq!(
+            "tcp://10.0.0.1:2376",
+            parse_host("tcp://10.0.0.1:2376").unwrap()
+        );
+        assert!(parse_host("ssh://user@host").is_err());
+
+        let address = Endpoint::new("/var/run/docker.sock").resolve().unwrap();
+
+        assert_eq!(Some("/var/run/docker.sock"), address.socket());
+        assert!(!address.rootless);
+        assert_eq!(
+            vec![("DOCKER_HOST", "unix:///var/run/docker.sock".to_string())],
+            address.env()
+        );
+    }
+
+    #[test]
+    fn endpoint_certs_checked_success() {
+        let mut cert_path = std::env::temp_dir();
+
+        cert_path.push("debian_bridge_endpoint_test");
+        std::fs::create_dir_all(&cert_path).unwrap();
+        std::fs::write(cert_path.join("cert.pem"), "").unwrap();
+        std::fs::write(cert_path.join("key.pem"), "").unwrap();
+
+        assert!(check_certs(&cert_path, false).is_ok());
+        assert!(check_certs(&cert_path, true).is_err());
+
+        let endpoint = Endpoint {
+            host: Some("tcp://10.0.0.1:2376".to_string()),
+            cert_path: Some(cert_path.to_owned()),
+            tls_verify: false,
+        };
+        let address = endpoint.resolve().unwrap();
+
+        assert_eq!(None, address.socket());
+        assert!(address
+            .env()
+            .contains(&("DOCKER_TLS_VERIFY", String::new())));
+        assert!(Endpoint {
+            tls_verify: true,
+            ..endpoint
+        }
+        .resolve()
+        .is_err());
+
+        std::fs::remove_dir_all(&cert_path).unwrap();
+    }
+}
@@ -0,0 +1,286 @@
+use super::{AppResult, BuildOutput, ContainerInfo, Engine, ImageInfo};
+use crate::app::error::AppError;
+use regex::Regex;
+use serde_json::json;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    os::unix::process::ExitStatusExt,
+    path::Path,
+    process::{ExitStatus, Output},
+};
+
+/// In-memory engine for tests. Images get labels from the `LABEL` instruction of a
+/// Dockerfile in a build context, built Dockerfiles and commands passed to `run` and
+/// `output` are recorded
+#[derive(Default)]
+pub struct FakeEngine {
+    images: RefCell<Vec<ImageInfo>>,
+    containers: RefCell<Vec<ContainerInfo>>,
+    commands: RefCell<Vec<Vec<String>>>,
+    dockerfiles: RefCell<Vec<String>>,
+    failures: RefCell<Vec<String>>,
+    last_id: RefCell<usize>,
+}
+
+impl FakeEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes every following call of an operation (e.g. "build", "tag") fail
+    pub fn fail<T: Into<String>>(&self, operation: T) -> &Self {
+        self.failures.borrow_mut().push(operation.into());
+        self
+    }
+
+    pub fn add_container(&self, container: ContainerInfo) -> &Self {
+        self.containers.borrow_mut().push(container);
+        self
+    }
+
+    pub fn commands(&self) -> Vec<Vec<String>> {
+        self.commands.borrow().to_owned()
+    }
+
+    pub fn dockerfiles(&self) -> Vec<String> {
+        self.dockerfiles.borrow().to_owned()
+    }
+
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self
+            .images
+            .borrow()
+            .iter()
+            .flat_map(|image| image.tags.to_owned())
+            .collect();
+
+        tags.sort();
+        tags
+    }
+
+    fn check(&self, operation: &str) -> AppResult<()> {
+        match self.failures.borrow().iter().any(|f| f == operation) {
+            true => Err(AppError::Docker),
+            false => Ok(()),
+        }
+    }
+
+    fn next_id(&self) -> String {
+        let mut id = self.last_id.borrow_mut();
+
+        *id += 1;
+        format!("sha256:{:064}", id)
+    }
+
+    /// Whether a container was started from an image, by id or by any of its tags
+    fn is_used(&self, image: &ImageInfo) -> bool {
+        self.containers
+            .borrow()
+            .iter()
+            .any(|c| c.image == image.id || image.tags.contains(&normalize(&c.image)))
+    }
+
+    fn untag(&self, tag: &str) {
+        let mut images = self.images.borrow_mut();
+
+        images
+            .iter_mut()
+            .for_each(|image| image.tags.retain(|t| t != tag));
+        images.retain(|image| !image.tags.is_empty());
+    }
+}
+
+fn normalize(name: &str) -> String {
+    match name.contains(':') {
+        true => name.to_string(),
+        false => format!("{}:latest", name),
+    }
+}
+
+fn parse_labels(dockerfile: &str) -> HashMap<String, String> {
+    let pattern = Regex::new(r#"([^\s=]+)="((?:[^"\\]|\\.)*)""#).unwrap();
+
+    dockerfile
+        .lines()
+        .filter(|line| line.starts_with("LABEL "))
+        .flat_map(|line| {
+            pattern
+                .captures_iter(line)
+                .map(|caps| (caps[1].to_string(), caps[2].replace("\\\"", "\"")))
+                .collect::<Vec<(String, String)>>()
+        })
+        .collect()
+}
+
+impl Engine for FakeEngine {
+    fn name(&self) -> String {
+        "Fake".to_string()
+    }
+
+    fn version(&self) -> AppResult<String> {
+        self.check("version").map(|_| "1.0".to_string())
+    }
+
+    fn build(
+        &self,
+        context: &Path,
+        tag: &str,
+        _nocache: bool,
+        mut output: BuildOutput,
+    ) -> AppResult<()> {
+        let dockerfile = std::fs::read_to_string(context.join("Dockerfile"))
+            .map_err(|err| AppError::File(err.to_string()))?;
+
+        output(&json!({ "stream": "Step 1/1 : FROM debian:9-slim\n" }))?;
+        self.check("build")?;
+        self.untag(tag);
+
+        let image = ImageInfo {
+            id: self.next_id(),
+            tags: vec![tag.to_string()],
+            labels: parse_labels(&dockerfile),
+        };
+
+        self.images.borrow_mut().push(image);
+        self.dockerfiles.borrow_mut().push(dockerfile);
+        Ok(())
+    }
+
+    fn pull(&self, _image: &str) -> AppResult<()> {
+        self.check("pull")
+    }
+
+    fn tag(&self, source: &str, target: &str) -> AppResult<()> {
+        self.check("tag")?;
+
+        let source = normalize(source);
+        let target = normalize(target);
+        let id = self
+            .images
+            .borrow()
+            .iter()
+            .find(|image| image.tags.contains(&source))
+            .map(|image| image.id.to_owned())
+            .ok_or(AppError::DockerStatus(404))?;
+
+        self.untag(&target);
+        self.images
+            .borrow_mut()
+            .iter_mut()
+            .filter(|image| image.id == id)
+            .for_each(|image| image.tags.push(target.to_owned()));
+
+        Ok(())
+    }
+
+    fn images(&self) -> AppResult<Vec<ImageInfo>> {
+        self.check("images")?;
+        Ok(self.images.borrow().to_owned())
+    }
+
+    fn delete_image(&self, name: &str) -> AppResult<()> {
+        self.check("delete_image")?;
+
+        let tag = normalize(name);
+        let image = self
+            .images
+            .borrow()
+            .iter()
+            .find(|image| image.id == name || image.tags.contains(&tag))
+            .cloned()
+            .ok_or(AppError::DockerStatus(404))?;
+
+        if image.id == name || image.tags.len() == 1 {
+            if self.is_used(&image) {
+                return Err(AppError::DockerStatus(409));
+            }
+
+            self.images.borrow_mut().retain(|i| i.id != image.id);
+            return Ok(());
+        }
+
+        Ok(self.untag(&tag))
+    }
+
+    fn containers(&self, all: bool) -> AppResult<Vec<ContainerInfo>> {
+        self.check("containers")?;
+        Ok(self
+            .containers
+            .borrow()
+            .iter()
+            .filter(|c| all || c.state == "running")
+            .cloned()
+            .collect())
+    }
+
+    fn delete_container(&self, id: &str) -> AppResult<()> {
+        self.check("delete_container")?;
+        self.containers.borrow_mut().retain(|c| c.id != id);
+        Ok(())
+    }
+
+    fn export(&self, image: &str, path: &Path) -> AppResult<()> {
+        self.check("export")?;
+
+        let image = normalize(image);
+        let labels = self
+            .images
+            .borrow()
+            .iter()
+            .find(|i| i.tags.contains(&image))
+            .map(|i| i.labels.to_owned())
+            .ok_or(AppError::DockerStatus(404))?;
+
+        std::fs::write(path, json!({ "tag": image, "labels": labels }).to_string())
+            .map_err(|err| AppError::File(err.to_string()))
+    }
+
+    fn import(&self, path: &Path) -> AppResult<()> {
+        self.check("import")?;
+
+        let data: serde_json::Value = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .ok_or(AppError::Program("Invalid image".to_string()))?;
+        let image = ImageInfo {
+            id: self.next_id(),
+            tags: vec![data["tag"].as_str().unwrap_or_default().to_string()],
+            labels: serde_json::from_value(data["labels"].to_owned()).unwrap_or_default(),
+        };
+
+        self.untag(&image.tags[0]);
+        self.images.borrow_mut().push(image);
+        Ok(())
+    }
+
+    fn run(&self, args: &Vec<String>) -> AppResult<ExitStatus> {
+        self.check("run")?;
+        self.commands.borrow_mut().push(args.to_owned());
+        Ok(ExitStatus::from_raw(0))
+    }
+
+    fn output(&self, args: &Vec<String>) -> AppResult<Output> {
+        self.check("output")?;
+        self.commands.borrow_mut().push(args.to_owned());
+        Ok(Output {
+            status: ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_parse_success() {
+        let labels =
+            parse_labels("FROM debian:9-slim\nLABEL a.b=\"foo bar\" c=\"say \\\"hi\\\"\"\nCMD foo");
+
+        assert_eq!(Some(&"foo bar".to_string()), labels.get("a.b"));
+        assert_eq!(Some(&"say \"hi\"".to_string()), labels.get("c"));
+    }
+}
@@ -0,0 +1,167 @@
+mod docker;
+mod endpoint;
+#[cfg(test)]
+pub mod fake;
+mod podman;
+
+use crate::{app::error::AppError, Executor};
+pub use docker::DockerEngine;
+pub use endpoint::{Address, Endpoint};
+pub use podman::PodmanEngine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use shiplift::Docker;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Output},
+};
+
+type AppResult<T> = Result<T, AppError>;
+
+/// Callback receiving build output in docker JSON stream format
+pub type BuildOutput = Box<dyn FnMut(&Value) -> AppResult<()> + Send>;
+
+/// Container engine used to build and run programs
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EngineKind {
+    Docker,
+    Podman,
+}
+
+impl Default for EngineKind {
+    fn default() -> Self {
+        EngineKind::Docker
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageInfo {
+    pub id: String,
+    pub tags: Vec<String>,
+    pub labels: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContainerInfo {
+    pub id: String,
+    pub names: Vec<String>,
+    pub image: String,
+    pub labels: HashMap<String, String>,
+    pub state: String,
+}
+
+/// Operations debian_bridge needs from a container engine
+pub trait Engine {
+    /// Human readable engine name
+    fn name(&self) -> String;
+
+    /// Address of the engine API, if the engine is reached through one
+    fn endpoint(&self) -> Option<String> {
+        None
+    }
+
+    fn version(&self) -> AppResult<String>;
+
+    fn build(&self, context: &Path, tag: &str, nocache: bool, output: BuildOutput)
+        -> AppResult<()>;
+
+    fn pull(&self, image: &str) -> AppResult<()>;
+
+    fn tag(&self, source: &str, target: &str) -> AppResult<()>;
+
+    fn images(&self) -> AppResult<Vec<ImageInfo>>;
+
+    fn delete_image(&self, name: &str) -> AppResult<()>;
+
+    /// Lists running containers, or all of them with `all`
+    fn containers(&self, all: bool) -> AppResult<Vec<ContainerInfo>>;
+
+    fn delete_container(&self, id: &str) -> AppResult<()>;
+
+    fn export(&self, image: &str, path: &Path) -> AppResult<()>;
+
+    fn import(&self, path: &Path) -> AppResult<()>;
+
+    /// Runs an engine command attached to the terminal, e.g. `run -ti ...`
+    fn run(&self, args: &Vec<String>) -> AppResult<ExitStatus>;
+
+    /// Runs an engine command and captures its output
+    fn output(&self, args: &Vec<String>) -> AppResult<Output>;
+}
+
+/// Connects to a configured engine. Docker is reached through a configured endpoint,
+/// podman is used through its docker compatible socket when the service is running, and
+/// through its command line otherwise. Podman doesn't accept an endpoint. The environment
+/// of the process isn't changed, TLS variables of `Address::tls_env` must be exported by
+/// an application before it starts other threads
+///
+/// # Example
+/// ```no_run
+/// use debian_bridge_core::{engine, Endpoint, EngineKind, Executor, System};
+///
+/// let executor = Executor::new().unwrap();
+/// let endpoint = Endpoint::new("tcp://10.0.0.1:2376");
+/// let engine = engine::connect(&EngineKind::Docker, &endpoint, &executor).unwrap();
+/// let system = System::try_new(engine.as_ref()).unwrap();
+/// ```
+pub fn connect(
+    kind: &EngineKind,
+    endpoint: &Endpoint,
+    executor: &Executor,
+) -> AppResult<Box<dyn Engine>> {
+    match kind {
+        EngineKind::Docker => {
+            let address = endpoint.resolve()?;
+            let docker = match address.socket() {
+                Some(socket) => Docker::unix(socket),
+                None => Docker::host(address.uri()?),
+            };
+
+            if address
+                .tls_env()
+                .iter()
+                .any(|(name, value)| std::env::var(name).unwrap_or_default() != *value)
+            {
+                return Err(AppError::Program(
+                    "TLS settings of the endpoint must be exported before the runtime starts"
+                        .to_string(),
+                ));
+            }
+
+            debug!("Docker host: {}", address);
+
+            Ok(Box::new(
+                DockerEngine::new(docker, executor)
+                    .with_endpoint(address.to_string())
+                    .with_env(address.env()),
+            ))
+        }
+        EngineKind::Podman if *endpoint != Endpoint::default() => Err(AppError::Program(
+            "Docker endpoint can't be used with podman, set `engine` to docker".to_string(),
+        )),
+        EngineKind::Podman => match get_podman_socket() {
+            Some(socket) => {
+                debug!("Podman socket: {}", socket.display());
+
+                Ok(Box::new(
+                    DockerEngine::new(Docker::unix(socket.to_string_lossy()), executor)
+                        .with_cli("podman")
+                        .with_endpoint(format!("unix://{}", socket.display())),
+                ))
+            }
+            None => Ok(Box::new(PodmanEngine::new())),
+        },
+    }
+}
+
+fn get_podman_socket() -> Option<PathBuf> {
+    let rootless = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(|dir| Path::new(&dir).join("podman").join("podman.sock"));
+
+    rootless
+        .into_iter()
+        .chain(Some(PathBuf::from("/run/podman/podman.sock")))
+        .find(|socket| socket.exists())
+}
@@ -0,0 +1,328 @@
+use super::{AppResult, BuildOutput, ContainerInfo, Engine, ImageInfo};
+use crate::app::error::AppError;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    process::{Child, Command, ExitStatus, Output, Stdio},
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+/// Registry podman adds to names of locally built images
+const LOCAL_REGISTRY: &str = "localhost/";
+
+/// Engine driving the podman command line, used when the podman service isn't running
+pub struct PodmanEngine {
+    cli: String,
+}
+
+impl Default for PodmanEngine {
+    fn default() -> Self {
+        PodmanEngine {
+            cli: "podman".to_string(),
+        }
+    }
+}
+
+impl PodmanEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn call(&self, args: &[&str]) -> AppResult<String> {
+        let output = self.output(&args.iter().map(|arg| arg.to_string()).collect())?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+
+            warn!("{}", stderr.trim());
+
+            return Err(
+                match stderr.contains("no such") || stderr.contains("not known") {
+                    true => AppError::DockerStatus(404),
+                    false => AppError::Docker,
+                },
+            );
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Passes lines of build stdout and stderr to the output, returns lines of stderr
+    fn stream_build(child: &mut Child, output: &mut BuildOutput) -> AppResult<Vec<String>> {
+        let (sender, receiver) = mpsc::channel();
+        let mut errors = vec![];
+
+        forward_lines(child.stdout.take(), false, sender.clone());
+        forward_lines(child.stderr.take(), true, sender);
+
+        for (is_error, line) in receiver {
+            output(&json!({ "stream": format!("{}\n", line) }))?;
+
+            if is_error {
+                errors.push(line);
+            }
+        }
+
+        Ok(errors)
+    }
+
+    fn parse_images(output: &str) -> AppResult<Vec<ImageInfo>> {
+        Ok(parse_list(output)?
+            .iter()
+            .map(|image| ImageInfo {
+                id: get_string(image, &["Id", "id", "ID"]),
+                tags: get_strings(image, &["Names", "names", "RepoTags"])
+                    .into_iter()
+                    .filter(|tag| !tag.starts_with("<none>"))
+                    .map(|tag| tag.trim_start_matches(LOCAL_REGISTRY).to_string())
+                    .collect(),
+                labels: get_labels(image),
+            })
+            .collect())
+    }
+
+    fn parse_containers(output: &str) -> AppResult<Vec<ContainerInfo>> {
+        Ok(parse_list(output)?
+            .iter()
+            .map(|container| ContainerInfo {
+                id: get_string(container, &["Id", "ID", "id"]),
+                names: get_strings(container, &["Names", "names"])
+                    .into_iter()
+                    .map(|name| format!("/{}", name.trim_start_matches('/')))
+                    .collect(),
+                image: get_string(container, &["Image", "image"]),
+                labels: get_labels(container),
+                state: get_string(container, &["State", "state", "Status"]).to_lowercase(),
+            })
+            .collect())
+    }
+}
+
+impl Engine for PodmanEngine {
+    fn name(&self) -> String {
+        "Podman".to_string()
+    }
+
+    fn version(&self) -> AppResult<String> {
+        self.call(&["version", "--format", "{{.Client.Version}}"])
+            .map(|version| version.trim().to_string())
+    }
+
+    fn build(
+        &self,
+        context: &Path,
+        tag: &str,
+        nocache: bool,
+        mut output: BuildOutput,
+    ) -> AppResult<()> {
+        let mut args = vec!["build", "-t", tag];
+
+        if nocache {
+            args.push("--no-cache");
+        }
+
+        let context = context.to_string_lossy();
+        let mut child = Command::new(&self.cli)
+            .args(&args)
+            .arg(context.as_ref())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|_| AppError::Docker)?;
+        let errors = Self::stream_build(&mut child, &mut output);
+
+        if errors.is_err() {
+            child.kill().unwrap_or_default();
+        }
+
+        let status = child.wait();
+        let errors = errors?;
+
+        match status {
+            Ok(status) if status.success() => Ok(()),
+            _ => {
+                // reported like a daemon build error, so it ends up in the build log
+                output(&json!({ "error": errors.join("\n") }))?;
+                Err(AppError::Docker)
+            }
+        }
+    }
+
+    fn pull(&self, image: &str) -> AppResult<()> {
+        self.call(&["pull", image]).map(|_| ())
+    }
+
+    fn tag(&self, source: &str, target: &str) -> AppResult<()> {
+        self.call(&["tag", source, target]).map(|_| ())
+    }
+
+    fn images(&self) -> AppResult<Vec<ImageInfo>> {
+        Self::parse_images(&self.call(&["images", "--format", "json"])?)
+    }
+
+    fn delete_image(&self, name: &str) -> AppResult<()> {
+        self.call(&["rmi", name]).map(|_| ())
+    }
+
+    fn containers(&self, all: bool) -> AppResult<Vec<ContainerInfo>> {
+        let output = match all {
+            true => self.call(&["ps", "--all", "--format", "json"])?,
+            false => self.call(&["ps", "--format", "json"])?,
+        };
+
+        Self::parse_containers(&output)
+    }
+
+    fn delete_container(&self, id: &str) -> AppResult<()> {
+        self.call(&["rm", id]).map(|_| ())
+    }
+
+    fn export(&self, image: &str, path: &Path) -> AppResult<()> {
+        self.call(&["save", "-o", &path.to_string_lossy(), image])
+            .map(|_| ())
+    }
+
+    fn import(&self, path: &Path) -> AppResult<()> {
+        self.call(&["load", "-i", &path.to_string_lossy()])
+            .map(|_| ())
+    }
+
+    fn run(&self, args: &Vec<String>) -> AppResult<ExitStatus> {
+        debug!("{} arguments: {:?}", self.cli, args);
+
+        Command::new(&self.cli)
+            .args(args)
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .status()
+            .map_err(|err| {
+                error!("{}", err.to_string());
+                AppError::Docker
+            })
+    }
+
+    fn output(&self, args: &Vec<String>) -> AppResult<Output> {
+        Command::new(&self.cli)
+            .args(args)
+            .output()
+            .map_err(|_| AppError::Docker)
+    }
+}
+
+/// Sends lines of a child process pipe from a separate thread, so both pipes are read
+fn forward_lines<R: Read + Send + 'static>(
+    pipe: Option<R>,
+    is_error: bool,
+    sender: Sender<(bool, String)>,
+) {
+    if let Some(pipe) = pipe {
+        thread::spawn(move || {
+            for line in BufReader::new(pipe).lines() {
+                match line {
+                    Ok(line) => sender.send((is_error, line)).unwrap_or_default(),
+                    Err(_) => break,
+                }
+            }
+        });
+    }
+}
+
+fn parse_list(output: &str) -> AppResult<Vec<Value>> {
+    if output.trim().is_empty() {
+        return Ok(vec![]);
+    }
+
+    serde_json::from_str(output).map_err(|err| AppError::Program(err.to_string()))
+}
+
+fn get_field<'a>(value: &'a Value, keys: &[&str]) -> Option<&'a Value> {
+    keys.iter().find_map(|key| value.get(key))
+}
+
+fn get_string(value: &Value, keys: &[&str]) -> String {
+    get_field(value, keys)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Reads a list of strings, older podman versions print a single string instead
+fn get_strings(value: &Value, keys: &[&str]) -> Vec<String> {
+    match get_field(value, keys) {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        Some(Value::String(item)) => vec![item.to_owned()],
+        _ => vec![],
+    }
+}
+
+fn get_labels(value: &Value) -> HashMap<String, String> {
+    get_field(value, &["Labels", "labels"])
+        .and_then(Value::as_object)
+        .map(|labels| {
+            labels
+                .iter()
+                .filter_map(|(key, value)| Some((key.to_owned(), value.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn podman_lists_parse_success() {
+        let images = PodmanEngine::parse_images(
+            r#"[
+                {"Id": "abc", "Names": ["localhost/foo:latest"], "Labels": {"a": "b"}},
+                {"id": "def", "names": null, "labels": null}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(2, images.len());
+        assert_eq!("abc", images[0].id);
+        assert_eq!(vec!["foo:latest".to_string()], images[0].tags);
+        assert_eq!(Some(&"b".to_string()), images[0].labels.get("a"));
+        assert!(images[1].tags.is_empty());
+
+        let containers = PodmanEngine::parse_containers(
+            r#"[{"ID": "123", "Names": "foo", "Image": "foo:latest", "Status": "Running"}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(vec!["/foo".to_string()], containers[0].names);
+        assert_eq!("running", containers[0].state);
+        assert!(PodmanEngine::parse_containers("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn build_stderr_streamed_success() {
+        let lines = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let streamed = lines.clone();
+        let mut output: BuildOutput = Box::new(move |value: &Value| {
+            streamed.lock().unwrap().push(value["stream"].to_string());
+            Ok(())
+        });
+        let mut child = Command::new("sh")
+            .args(&["-c", "echo STEP 1; echo Error: no such image >&2"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let errors = PodmanEngine::stream_build(&mut child, &mut output).unwrap();
+
+        child.wait().unwrap();
+        assert_eq!(vec!["Error: no such image".to_string()], errors);
+        assert_eq!(2, lines.lock().unwrap().len());
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::{mpsc, Arc};
+use tokio::{
+    prelude::Future,
+    runtime::{Runtime, TaskExecutor},
+};
+
+/// Tokio runtime running all calls to a docker daemon. Results are awaited on the calling
+/// thread without entering an executor, so the core API can be called from tasks of other
+/// runtimes
+///
+/// # Example
+/// ```no_run
+/// use debian_bridge_core::{engine::DockerEngine, Docker, Executor, System};
+///
+/// let executor = Executor::new().unwrap();
+/// let system = System::try_new(&DockerEngine::new(Docker::new(), &executor)).unwrap();
+/// ```
+///
+/// Calls from a task of an application runtime:
+/// ```
+/// use debian_bridge_core::Executor;
+/// use tokio::prelude::{future, Future};
+///
+/// tokio::run(future::lazy(|| {
+///     let executor = Executor::new().unwrap();
+///
+///     assert_eq!(Ok(2), executor.wait(future::ok::<_, ()>(1).map(|n| n + 1)));
+///     Ok(())
+/// }));
+/// ```
+#[derive(Clone)]
+pub struct Executor {
+    executor: TaskExecutor,
+    /// Keeps an owned runtime alive while clones of the executor exist
+    _runtime: Option<Arc<Runtime>>,
+}
+
+impl Executor {
+    /// Starts a runtime owned by the executor, it's shut down with the last clone
+    pub fn new() -> std::io::Result<Self> {
+        Runtime::new().map(Executor::from)
+    }
+
+    /// Spawns a future on the runtime and blocks the current thread until it's resolved
+    pub fn wait<F>(&self, future: F) -> Result<F::Item, F::Error>
+    where
+        F: Future + Send + 'static,
+        F::Item: Send + 'static,
+        F::Error: Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+
+        self.executor.spawn(future.then(move |result| {
+            sender.send(result).unwrap_or_default();
+            Ok(())
+        }));
+
+        receiver
+            .recv()
+            .unwrap_or_else(|_| panic!("The runtime was shut down before a future resolved"))
+    }
+}
+
+impl From<Runtime> for Executor {
+    fn from(runtime: Runtime) -> Self {
+        Executor {
+            executor: runtime.executor(),
+            _runtime: Some(Arc::new(runtime)),
+        }
+    }
+}
+
+/// Executor spawning futures on a runtime owned by the caller. Waiting blocks the calling
+/// thread, so a single threaded runtime can't be waited on from its own tasks
+impl From<TaskExecutor> for Executor {
+    fn from(executor: TaskExecutor) -> Self {
+        Executor {
+            executor,
+            _runtime: None,
+        }
+    }
+}
@@ -9,6 +9,8 @@ extern crate colorful;
 extern crate dirs;
 extern crate dockerfile;
 extern crate freedesktop_desktop_entry;
+extern crate hyper;
+extern crate percent_encoding;
 extern crate pipers;
 extern crate pretty_env_logger;
 extern crate regex;
@@ -17,8 +19,12 @@ extern crate shiplift;
 extern crate tokio;
 
 mod app;
+pub mod engine;
+mod executor;
 mod sys;
 
 pub use app::*;
+pub use engine::{Endpoint, Engine, EngineKind};
+pub use executor::Executor;
 pub use shiplift::Docker;
 pub use sys::System;
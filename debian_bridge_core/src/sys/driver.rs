@@ -1,4 +1,7 @@
-use std::fmt::{Display, Formatter, Result};
+use std::{
+    fmt::{Display, Formatter, Result},
+    path::{Path, PathBuf},
+};
 
 #[derive(Clone)]
 pub enum WindowManager {
@@ -23,6 +26,7 @@ impl Driver for WindowManager {}
 pub enum SoundDriver {
     Alsa,
     PulseAudio,
+    PipeWire,
 }
 
 impl Display for SoundDriver {
@@ -30,6 +34,7 @@ impl Display for SoundDriver {
         let val = match self {
             SoundDriver::Alsa => "Alsa",
             SoundDriver::PulseAudio => "PulseAudio",
+            SoundDriver::PipeWire => "PipeWire",
         };
 
         write!(f, "{}", val)
@@ -38,15 +43,101 @@ impl Display for SoundDriver {
 
 impl Driver for SoundDriver {}
 
+/// Class of host devices shared with a container
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeviceClass {
+    Camera,
+    Audio,
+    Usb,
+    Input,
+    Render,
+}
+
+impl DeviceClass {
+    pub fn all() -> Vec<DeviceClass> {
+        vec![
+            DeviceClass::Camera,
+            DeviceClass::Audio,
+            DeviceClass::Usb,
+            DeviceClass::Input,
+            DeviceClass::Render,
+        ]
+    }
+
+    /// Device nodes of the class on the host, directories are shared with all nodes inside
+    pub fn find(&self) -> Vec<PathBuf> {
+        match self {
+            DeviceClass::Camera => find_nodes("/dev", "video"),
+            DeviceClass::Audio => existing("/dev/snd"),
+            DeviceClass::Usb => existing("/dev/bus/usb"),
+            DeviceClass::Input => existing("/dev/input"),
+            DeviceClass::Render => find_nodes("/dev/dri", "renderD"),
+        }
+    }
+
+    /// Cgroup rule by the device major number, so devices plugged after a container start
+    /// are accessible too
+    pub fn cgroup_rule(&self) -> &'static str {
+        match self {
+            DeviceClass::Camera => "c 81:* rmw",
+            DeviceClass::Audio => "c 116:* rmw",
+            DeviceClass::Usb => "c 189:* rmw",
+            DeviceClass::Input => "c 13:* rmw",
+            DeviceClass::Render => "c 226:* rmw",
+        }
+    }
+}
+
+impl Display for DeviceClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let val = match self {
+            DeviceClass::Camera => "Camera",
+            DeviceClass::Audio => "Audio",
+            DeviceClass::Usb => "USB",
+            DeviceClass::Input => "Input",
+            DeviceClass::Render => "Render",
+        };
+
+        write!(f, "{}", val)
+    }
+}
+
+fn existing(path: &str) -> Vec<PathBuf> {
+    Some(PathBuf::from(path))
+        .filter(|path| path.exists())
+        .into_iter()
+        .collect()
+}
+
+fn find_nodes(dir: &str, prefix: &str) -> Vec<PathBuf> {
+    let mut nodes: Vec<PathBuf> = Path::new(dir)
+        .read_dir()
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_name().to_string_lossy().starts_with(prefix))
+                .map(|entry| entry.path())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    nodes.sort();
+    nodes
+}
+
 #[derive(Clone)]
-pub struct DockerVersion(pub String);
+pub struct EngineVersion {
+    pub name: String,
+    pub version: String,
+    pub endpoint: Option<String>,
+}
 
-impl Display for DockerVersion {
+impl Display for EngineVersion {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(f, "{}", self.0)
+        write!(f, "{} {}", self.name, self.version)
     }
 }
 
-impl Driver for DockerVersion {}
+impl Driver for EngineVersion {}
 
 pub trait Driver: Display + Clone {}
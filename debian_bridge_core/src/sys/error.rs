@@ -15,7 +15,7 @@ impl std::fmt::Display for SystemError {
             f,
             "{}",
             match self {
-                SystemError::DockerConnection => "Cannot connect to docker daemon",
+                SystemError::DockerConnection => "Cannot connect to a container engine",
             }
         )
     }
@@ -1,51 +1,119 @@
 pub mod driver;
 pub mod error;
 
+use crate::{app::util, Engine};
 use colorful::{Color, Colorful};
 use driver::*;
 use error::SystemError;
-use shiplift::{rep::Version, Docker};
 use std::{
     error::Error,
     ffi::OsString,
     fmt::{Display, Formatter},
     fs::File,
+    path::{Path, PathBuf},
     process::{Command, ExitStatus, Stdio},
 };
-use tokio::{prelude::Future, runtime::Runtime};
 
 type SystemResult<T> = Result<T, SystemError>;
 
+const CUPS_SOCKETS: &[&str] = &["/run/cups/cups.sock", "/var/run/cups/cups.sock"];
+/// D-Bus activation file installed with xdg-desktop-portal
+const PORTAL_SERVICE: &str = "/usr/share/dbus-1/services/org.freedesktop.portal.Desktop.service";
+
 #[derive(Clone)]
 pub struct System {
     pub wm: Option<WindowManager>,
     pub sd: Option<SoundDriver>,
-    pub docker_version: DockerVersion,
+    pub engine: EngineVersion,
+    /// Socket of a wayland compositor
+    pub wayland: Option<PathBuf>,
+    /// Socket of PulseAudio or pipewire-pulse
+    pub pulse: Option<PathBuf>,
+    /// Cookie authorizing PulseAudio clients
+    pub pulse_cookie: Option<PathBuf>,
+    /// Socket of PipeWire
+    pub pipewire: Option<PathBuf>,
+    /// Socket of a CUPS server
+    pub cups: Option<PathBuf>,
+    /// Whether XDG desktop portal can be reached through the session bus
+    pub portal: bool,
+    /// Device nodes found on the host with their classes
+    pub devices: Vec<(DeviceClass, PathBuf)>,
+    /// Socket of the SSH agent from `SSH_AUTH_SOCK`
+    pub ssh_agent: Option<PathBuf>,
+    /// Restricted socket of gpg-agent meant for forwarding, it can't manage keys
+    pub gpg_agent: Option<PathBuf>,
 }
 
 impl System {
-    pub fn try_new(docker: &Docker) -> SystemResult<Self> {
+    pub fn try_new(engine: &dyn Engine) -> SystemResult<Self> {
         Ok(Self {
             wm: Self::get_window_manager(),
             sd: Self::get_sound_driver(),
-            docker_version: Self::get_docker(docker)?,
+            engine: Self::get_engine(engine)?,
+            wayland: util::get_wayland_socket(),
+            pulse: util::get_pulse_socket(),
+            pulse_cookie: util::get_pulse_cookie(),
+            pipewire: util::get_pipewire_socket(),
+            cups: Self::get_cups_socket(),
+            portal: Self::has_portal(),
+            devices: Self::get_devices(),
+            ssh_agent: Self::get_ssh_agent(),
+            gpg_agent: Self::get_gpg_agent(),
         })
     }
 
-    fn get_docker(docker: &Docker) -> SystemResult<DockerVersion> {
-        let version = docker.version();
-        let mut rt = Runtime::new().unwrap();
+    fn get_cups_socket() -> Option<PathBuf> {
+        CUPS_SOCKETS
+            .iter()
+            .map(PathBuf::from)
+            .find(|socket| socket.exists())
+    }
 
-        let result = match rt.block_on(version) {
-            Ok(Version { api_version: v, .. }) => Ok(DockerVersion(v.to_owned())),
-            Err(err) => Err(SystemError::DockerConnection),
-        };
+    fn has_portal() -> bool {
+        std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some() && Path::new(PORTAL_SERVICE).exists()
+    }
 
-        rt.shutdown_now()
-            .wait()
-            .map_err(|err| SystemError::DockerConnection)?;
+    fn get_devices() -> Vec<(DeviceClass, PathBuf)> {
+        DeviceClass::all()
+            .into_iter()
+            .flat_map(|class| {
+                class
+                    .find()
+                    .into_iter()
+                    .map(move |node| (class.to_owned(), node))
+            })
+            .collect()
+    }
 
-        result
+    fn get_ssh_agent() -> Option<PathBuf> {
+        std::env::var_os("SSH_AUTH_SOCK")
+            .map(PathBuf::from)
+            .filter(|socket| socket.is_absolute() && socket.exists())
+    }
+
+    fn get_gpg_agent() -> Option<PathBuf> {
+        let output = Command::new("gpgconf")
+            .args(&["--list-dirs", "agent-extra-socket"])
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+
+        Some(PathBuf::from(
+            String::from_utf8_lossy(&output.stdout).trim(),
+        ))
+        .filter(|socket| socket.is_absolute() && socket.exists())
+    }
+
+    fn get_engine(engine: &dyn Engine) -> SystemResult<EngineVersion> {
+        engine
+            .version()
+            .map(|version| EngineVersion {
+                name: engine.name(),
+                version,
+                endpoint: engine.endpoint(),
+            })
+            .map_err(|err| SystemError::DockerConnection)
     }
 
     fn get_window_manager() -> Option<WindowManager> {
@@ -59,6 +127,10 @@ impl System {
     }
 
     fn get_sound_driver() -> Option<SoundDriver> {
+        if util::get_pipewire_socket().is_some() {
+            return Some(SoundDriver::PipeWire);
+        }
+
         let pulse = Command::new("pactl")
             .arg("list")
             .stdout(Stdio::null())
@@ -80,9 +152,13 @@ impl Display for System {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         writeln!(
             f,
-            "\n\n\tDocker version  ===> {docker_version}\n\tWindow manager  ===> \
-             {window_manager}\n\tSound driver    ===> {sound_driver}",
-            docker_version = DisplayOption(Some(self.docker_version.to_owned())),
+            "\n\n\tEngine          ===> {engine}\n\tEndpoint        ===> {endpoint}\n\tWindow \
+             manager  ===> {window_manager}\n\tSound driver    ===> {sound_driver}",
+            engine = DisplayOption(Some(self.engine.to_owned())),
+            endpoint = match &self.engine.endpoint {
+                Some(endpoint) => endpoint.to_owned().color(Color::Green),
+                None => "None".color(Color::Red),
+            },
             window_manager = DisplayOption(self.wm.to_owned()),
             sound_driver = DisplayOption(self.sd.to_owned()),
         )